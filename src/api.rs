@@ -0,0 +1,276 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{fmt, io};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{
+    cli::{Output, read_limited},
+    config::{self, ApiConfigPayload, CONFIG_SCHEMA, Config},
+    errors::{Error, ErrorCode, Result},
+    magic_mount::{self, plan::Plan},
+    misc::init_list,
+    paths::Paths,
+    report::Report,
+    scanner::{self, MODULE_SCHEMA, ModuleRules},
+    utils::validate_module_id,
+    validate,
+};
+
+/// The request and response format. Bumped when either changes incompatibly.
+pub const API_VERSION: u32 = 1;
+
+const METHODS: [&str; 12] = [
+    "capabilities",
+    "version",
+    "config.get",
+    "config.set",
+    "config.reset",
+    "modules.list",
+    "module_rules.get",
+    "module_rules.set",
+    "plan",
+    "conflicts",
+    "report",
+    "validate",
+];
+
+/// What this build understands beyond the methods themselves.
+const FEATURES: [&str; 10] = [
+    "paths",
+    "module_lists",
+    "module_rules",
+    "module_contexts",
+    "custom_list_conditions",
+    "custom_list_variables",
+    "custom_list_includes",
+    "custom_list_directives",
+    "bind_options",
+    "bind_contexts",
+];
+
+/// `{"method": "...", "params": {...}, "version": 1}`; `params` and `version`
+/// may be left out.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Request {
+    method: String,
+    #[serde(default)]
+    params: Value,
+    version: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+/// `{"ok": true, "version": 1, "result": ...}` or `{"ok": false, "version": 1,
+/// "error": {"code": "...", "message": "...", "details": ...}}`.
+#[derive(Debug, Serialize)]
+pub struct Response {
+    pub ok: bool,
+    pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ApiError>,
+}
+
+impl ApiError {
+    fn new<M>(code: ErrorCode, message: M) -> Self
+    where
+        M: fmt::Display,
+    {
+        Self {
+            code,
+            message: message.to_string(),
+            details: None,
+        }
+    }
+
+    fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        let code = match error {
+            Error::InvalidModuleID { .. } => ErrorCode::InvalidParams,
+            _ => ErrorCode::Failed,
+        };
+        let chain = anyhow::Error::from(error);
+        let details = chain
+            .chain()
+            .skip(1)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let api_error = Self::new(code, &chain);
+        if details.is_empty() {
+            api_error
+        } else {
+            api_error.with_details(json!({ "causes": details }))
+        }
+    }
+}
+
+impl From<std::result::Result<Value, ApiError>> for Response {
+    fn from(result: std::result::Result<Value, ApiError>) -> Self {
+        match result {
+            Ok(result) => Self {
+                ok: true,
+                version: API_VERSION,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => Self {
+                ok: false,
+                version: API_VERSION,
+                result: None,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+fn params<T>(method: &str, params: Value) -> std::result::Result<T, ApiError>
+where
+    T: DeserializeOwned,
+{
+    // a method without parameters may be sent `null`
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| {
+        ApiError::new(ErrorCode::InvalidParams, e).with_details(json!({ "method": method }))
+    })
+}
+
+fn to_value<T>(value: &T) -> std::result::Result<Value, ApiError>
+where
+    T: Serialize,
+{
+    serde_json::to_value(value).map_err(|e| ApiError::from(Error::from(e)))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModuleParams {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetRulesParams {
+    id: String,
+    rules: ModuleRules,
+}
+
+fn capabilities() -> Value {
+    json!({
+        "api_version": API_VERSION,
+        "version": env!("CARGO_PKG_VERSION"),
+        "methods": METHODS,
+        "features": FEATURES,
+        "schemas": {
+            "config": CONFIG_SCHEMA,
+            "module": MODULE_SCHEMA,
+        },
+    })
+}
+
+fn call(method: &str, params_value: Value, paths: &Paths) -> std::result::Result<Value, ApiError> {
+    let done = || Ok(json!({ "saved": true }));
+    let config = || Config::load_or_default(&paths.config_file);
+    match method {
+        "capabilities" => Ok(capabilities()),
+        "version" => Ok(json!({ "version": env!("CARGO_PKG_VERSION") })),
+        "config.get" => to_value(&config::api_config(paths)),
+        "config.set" => {
+            let payload: ApiConfigPayload = params(method, params_value)?;
+            config::save_api_config(paths, payload)?;
+            done()
+        }
+        "config.reset" => {
+            config::reset_config(paths)?;
+            done()
+        }
+        "modules.list" => {
+            let modules =
+                scanner::list_modules(&paths.module_dir, &paths.rules_dir(), &config().partitions);
+            to_value(&scanner::show_modules(modules, paths.mount_status())?)
+        }
+        "module_rules.get" => {
+            let ModuleParams { id } = params(method, params_value)?;
+            validate_module_id(&id)?;
+            to_value(&ModuleRules::load(paths.rules_dir(), &id))
+        }
+        "module_rules.set" => {
+            let SetRulesParams { id, rules } = params(method, params_value)?;
+            validate_module_id(&id)?;
+            rules.save(paths.rules_dir(), &id)?;
+            done()
+        }
+        "plan" => {
+            init_list(paths);
+            to_value(&Plan::new(paths, &config())?)
+        }
+        "conflicts" => {
+            init_list(paths);
+            to_value(&magic_mount::conflicts(paths, &config())?)
+        }
+        "report" => to_value(&Report::load(paths.last_run_report())?),
+        "validate" => to_value(&validate::validate(paths)),
+        _ => Err(ApiError::new(
+            ErrorCode::UnknownMethod,
+            format!("unknown method {method:?}"),
+        )
+        .with_details(json!({ "methods": METHODS }))),
+    }
+}
+
+/// Answers one request given as its JSON text.
+pub fn respond(request: &str, paths: &Paths) -> Response {
+    let request: Request = match serde_json::from_str(request) {
+        Ok(request) => request,
+        Err(e) => return Err(ApiError::new(ErrorCode::InvalidRequest, e)).into(),
+    };
+
+    match request.version {
+        Some(version) if version != API_VERSION => Err(ApiError::new(
+            ErrorCode::UnsupportedVersion,
+            format!("api version {version} is not supported"),
+        )
+        .with_details(json!({ "supported": [API_VERSION] })))
+        .into(),
+        _ => call(&request.method, request.params, paths).into(),
+    }
+}
+
+/// `api [<request json>]`, reading the request from stdin when not given.
+/// Prints the response and fails when it is an error.
+pub fn handle_api(request: Option<&str>, paths: &Paths, output: Output) -> Result<()> {
+    let request = match request {
+        Some(request) => request.to_string(),
+        None => read_limited(io::stdin().lock(), "stdin")?,
+    };
+
+    let response = respond(&request, paths);
+    output.json(&response)?;
+    match response.error {
+        Some(error) => Err(Error::Api {
+            code: error.code,
+            message: error.message,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/unit/api.rs"]
+mod tests;
@@ -7,14 +7,16 @@ use std::{
     path::{Component, Path, PathBuf},
 };
 
-use rustix::mount::{MountFlags, UnmountFlags, mount_bind, mount_move, mount_remount, unmount};
+use rustix::mount::{MountFlags, MountPropagationFlags, UnmountFlags};
 
 use crate::{
+    defs,
     errors::Result,
-    magic_mount::utils::mount_mirror,
+    magic_mount::{plan::Operation, utils::mount_mirror},
+    mount_backend::MountBackend,
     mount_list,
-    parser::{COMMAND_LIST, MountType},
-    utils::ksucalls::send_unmountable,
+    parser::{BindOptions, COMMAND_LIST, MountType, Propagation},
+    utils::{ksucalls::send_unmountable, lsetfilecon},
 };
 
 fn mirror_paths(target: &Path) -> Result<(PathBuf, PathBuf)> {
@@ -107,14 +109,72 @@ fn report_rollback(success: bool, on_failure: impl FnOnce()) {
     }
 }
 
-fn mount_readonly(
+fn propagation_flags(propagation: Propagation, recursive: bool) -> MountPropagationFlags {
+    let flags = match propagation {
+        Propagation::Private => MountPropagationFlags::PRIVATE,
+        Propagation::Slave => MountPropagationFlags::DOWNSTREAM,
+        Propagation::Shared => MountPropagationFlags::SHARED,
+    };
+    if recursive {
+        flags | MountPropagationFlags::REC
+    } else {
+        flags
+    }
+}
+
+/// Labels `path`, and everything in it if it is a directory, with `context`.
+fn relabel(path: &Path, context: &str, backend: &dyn MountBackend) -> Result<()> {
+    lsetfilecon(backend, path, context)?;
+    if path.is_dir() && !path.is_symlink() {
+        for entry in path.read_dir()?.flatten() {
+            relabel(&entry.path(), context, backend)?;
+        }
+    }
+    Ok(())
+}
+
+/// Binds `source` on `target`, read-only unless `options` say otherwise.
+fn mount_with_options(
     source: &Path,
     target: &Path,
+    options: &BindOptions,
     leaked_mounts: Option<&mount_list::MountList>,
+    backend: &dyn MountBackend,
 ) -> Result<()> {
-    mount_bind(source, target)?;
-    if let Err(error) = mount_remount(target, MountFlags::BIND | MountFlags::RDONLY, "") {
-        let rollback = unmount(target, UnmountFlags::DETACH);
+    if let Some(context) = &options.context {
+        log::info!(
+            "label {} as {context} for {}",
+            source.display(),
+            target.display()
+        );
+        relabel(source, context, backend)?;
+    }
+
+    if options.recursive {
+        backend.bind_recursive(source, target)?;
+    } else {
+        backend.bind(source, target)?;
+    }
+
+    let mut flags = MountFlags::BIND;
+    for (set, flag) in [
+        (!options.rw, MountFlags::RDONLY),
+        (options.nosuid, MountFlags::NOSUID),
+        (options.nodev, MountFlags::NODEV),
+        (options.noexec, MountFlags::NOEXEC),
+    ] {
+        if set {
+            flags |= flag;
+        }
+    }
+
+    let result = backend.remount(target, flags).and_then(|()| {
+        options.propagation.map_or(Ok(()), |propagation| {
+            backend.change(target, propagation_flags(propagation, options.recursive))
+        })
+    });
+    if let Err(error) = result {
+        let rollback = backend.unmount(target, UnmountFlags::DETACH);
         report_rollback(rollback.is_ok(), || {
             if let Some(mounts) = leaked_mounts {
                 mounts.record(target);
@@ -126,12 +186,18 @@ fn mount_readonly(
                 target.display()
             );
         }
-        return Err(error.into());
+        return Err(error);
     }
     Ok(())
 }
 
-fn mount_missing_target(source: &Path, target: &Path) -> Result<PathBuf> {
+fn mount_missing_target(
+    source: &Path,
+    target: &Path,
+    options: &BindOptions,
+    work_root: &Path,
+    backend: &dyn MountBackend,
+) -> Result<PathBuf> {
     let (ancestor, relative_target) = mirror_paths(target)?;
     let first_missing_component = relative_target
         .components()
@@ -154,24 +220,26 @@ fn mount_missing_target(source: &Path, target: &Path) -> Result<PathBuf> {
         validate_mirror_entry(entry)?;
     }
 
-    let workdir = tempfile::Builder::new().tempdir()?;
-    mount_bind(workdir.path(), workdir.path())?;
+    let workdir = tempfile::Builder::new()
+        .prefix(defs::BIND_WORK_DIR_PREFIX)
+        .tempdir_in(work_root)?;
+    backend.bind(workdir.path(), workdir.path())?;
 
     let result = (|| -> Result<()> {
         for entry in &entries {
-            mount_mirror(ancestor.as_path(), workdir.path(), entry)?;
+            mount_mirror(ancestor.as_path(), workdir.path(), entry, backend)?;
         }
 
         let mirror_target = workdir.path().join(relative_target);
         create_mirror_target(source, &mirror_target)?;
-        mount_readonly(source, &mirror_target, None)?;
-        mount_remount(workdir.path(), MountFlags::BIND | MountFlags::RDONLY, "")?;
-        mount_move(workdir.path(), &ancestor)?;
+        mount_with_options(source, &mirror_target, options, None, backend)?;
+        backend.remount(workdir.path(), MountFlags::BIND | MountFlags::RDONLY)?;
+        backend.move_mount(workdir.path(), &ancestor)?;
         Ok(())
     })();
 
     if let Err(error) = result {
-        if let Err(unmount_error) = unmount(workdir.path(), UnmountFlags::DETACH) {
+        if let Err(unmount_error) = backend.unmount(workdir.path(), UnmountFlags::DETACH) {
             log::error!(
                 "failed to clean up bind mount workdir {}: {unmount_error}",
                 workdir.path().display()
@@ -183,37 +251,77 @@ fn mount_missing_target(source: &Path, target: &Path) -> Result<PathBuf> {
     Ok(ancestor)
 }
 
-fn mount_target(source: &Path, target: &Path, mounts: &mount_list::MountList) -> Result<PathBuf> {
+fn mount_target(
+    source: &Path,
+    target: &Path,
+    options: &BindOptions,
+    work_root: &Path,
+    mounts: &mount_list::MountList,
+    backend: &dyn MountBackend,
+) -> Result<PathBuf> {
     match target.metadata() {
         Ok(_) => {
             let target = fs::canonicalize(target)?;
-            mount_readonly(source, &target, Some(mounts))?;
+            mount_with_options(source, &target, options, Some(mounts), backend)?;
             Ok(target)
         }
-        Err(error) if error.kind() == ErrorKind::NotFound => mount_missing_target(source, target),
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            mount_missing_target(source, target, options, work_root, backend)
+        }
         Err(error) => Err(error.into()),
     }
 }
 
-pub fn bind_mount(umount: bool, mounts: &mount_list::MountList) -> Result<()> {
+pub fn bind_mount(
+    umount: bool,
+    work_root: &Path,
+    mounts: &mount_list::MountList,
+    backend: &dyn MountBackend,
+) -> Result<()> {
     let commands = COMMAND_LIST
         .get()
         .ok_or_else(|| Error::new(ErrorKind::NotFound, "mount command list is not initialized"))?;
 
     for command in commands {
-        let MountType::Mount { source, target } = command else {
+        let MountType::Mount {
+            source,
+            target,
+            options,
+        } = &command.kind
+        else {
             continue;
         };
 
-        log::debug!("bind mount: {source} -> {target}");
+        match &command.module {
+            Some(module) => log::debug!("bind mount from {module}: {}", command.kind),
+            None => log::debug!("bind mount: {}", command.kind),
+        }
 
         let source = Path::new(source);
         if !source.exists() {
-            log::error!("source doesn't exist, skip: {}", source.display());
+            if options.optional {
+                log::debug!("optional source doesn't exist, skip: {}", source.display());
+            } else {
+                log::error!("source doesn't exist, skip: {}", source.display());
+            }
             continue;
         }
 
-        let unmount_target = mount_target(source, Path::new(target), mounts)?;
+        let unmount_target = match mount_target(
+            source,
+            Path::new(target),
+            options,
+            work_root,
+            mounts,
+            backend,
+        ) {
+            Ok(unmount_target) => unmount_target,
+            Err(e) if options.optional => {
+                log::warn!("optional bind mount {} failed: {e:#}", command.kind);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
         mounts.record(&unmount_target);
         if umount {
             send_unmountable(unmount_target);
@@ -223,6 +331,60 @@ pub fn bind_mount(umount: bool, mounts: &mount_list::MountList) -> Result<()> {
     Ok(())
 }
 
+/// Lists the operations `bind_mount` would perform, without mounting anything.
+pub fn plan(work_root: &Path) -> Result<Vec<Operation>> {
+    let commands = COMMAND_LIST
+        .get()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "mount command list is not initialized"))?;
+    let mut ops = Vec::new();
+
+    for command in commands {
+        let MountType::Mount { source, target, .. } = &command.kind else {
+            continue;
+        };
+
+        let source = PathBuf::from(source);
+        if !source.exists() {
+            ops.push(Operation::Skip { path: source });
+            continue;
+        }
+
+        let target = Path::new(target);
+        match target.metadata() {
+            Ok(_) => ops.push(Operation::Bind {
+                source,
+                target: fs::canonicalize(target)?,
+            }),
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                // the real work dir is a fresh temporary directory with this prefix
+                let (ancestor, relative_target) = mirror_paths(target)?;
+                let work_dir = work_root.join(defs::BIND_WORK_DIR_PREFIX);
+                ops.push(Operation::Tmpfs {
+                    path: ancestor.clone(),
+                    work_dir: work_dir.clone(),
+                });
+                for entry in ancestor.read_dir()?.flatten() {
+                    ops.push(Operation::Mirror {
+                        source: entry.path(),
+                        target: work_dir.join(entry.file_name()),
+                    });
+                }
+                ops.push(Operation::Bind {
+                    source,
+                    target: work_dir.join(relative_target),
+                });
+                ops.push(Operation::Move {
+                    source: work_dir,
+                    target: ancestor,
+                });
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Ok(ops)
+}
+
 #[cfg(test)]
 #[path = "../tests/unit/bind_mount.rs"]
 mod tests;
@@ -0,0 +1,212 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use hex::decode;
+use serde::Serialize;
+
+use crate::{
+    errors::{Error, Result},
+    paths::PathArgs,
+};
+
+/// Largest JSON payload read from any source, in bytes.
+pub const MAX_PAYLOAD_SIZE: usize = 4 << 20;
+
+/// Shown after the options in `--help`.
+const ENVIRONMENT: &str = "Path options fall back to MAGIC_MOUNT_MODULE_DIR, MAGIC_MOUNT_CONFIG, \
+MAGIC_MOUNT_STATE_DIR and MAGIC_MOUNT_WORK_ROOT.";
+
+/// Mounts module files over the system with magic mount. Without a command it
+/// performs the boot mount.
+#[derive(Debug, Parser)]
+#[command(version, long_about = None, after_help = ENVIRONMENT)]
+pub struct Cli {
+    #[command(flatten)]
+    pub paths: PathArgs,
+    /// Print JSON instead of text
+    #[arg(long, global = true, conflicts_with = "quiet")]
+    pub json: bool,
+    /// Print nothing but errors; the exit status tells the result
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Print the mount plan instead of mounting, same as `plan`
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Print the config, ignore list and custom mounts as JSON
+    ShowConfig,
+    /// Replace config fields and the managed custom list entries
+    SaveConfig {
+        /// JSON with the fields to change
+        #[command(flatten)]
+        payload: PayloadArgs,
+    },
+    /// Write the default config and an empty custom list
+    GenConfig,
+    /// Detach every mount recorded by the last boot
+    EmulatedSoftReboot,
+    /// List modules with their rules and mount status as JSON
+    Modules,
+    /// Read or change the mount rules of a module
+    ModuleRules {
+        #[command(subcommand)]
+        action: RulesAction,
+    },
+    /// List files provided by more than one module
+    Conflicts,
+    /// Print the mount operations a boot would perform
+    Plan,
+    /// Print the report of the last boot as JSON
+    Report,
+    /// Check the config and custom list for errors
+    Validate,
+    /// Print the version as JSON
+    Version,
+    /// Answer a versioned JSON API request, read from stdin when not given
+    Api {
+        /// {"method": "...", "params": {...}, "version": 1}
+        request: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RulesAction {
+    /// Print the rules of a module as JSON
+    Get { module_id: String },
+    /// Replace the rules of a module
+    Set {
+        module_id: String,
+        /// JSON rules
+        #[command(flatten)]
+        payload: PayloadArgs,
+    },
+}
+
+/// Where a command reads its JSON payload from; exactly one is required.
+#[derive(Debug, Default, Args)]
+#[group(required = true, multiple = false)]
+pub struct PayloadArgs {
+    /// Hex-encoded JSON
+    #[arg(long, value_name = "HEX")]
+    pub payload: Option<String>,
+    /// Read the JSON from a file
+    #[arg(long, value_name = "PATH")]
+    pub payload_file: Option<PathBuf>,
+    /// Read the JSON from stdin
+    #[arg(long)]
+    pub stdin: bool,
+}
+
+impl PayloadArgs {
+    /// The payload JSON text, from whichever source was given.
+    pub fn read(&self) -> Result<String> {
+        if let Some(payload) = &self.payload {
+            if payload.len() > MAX_PAYLOAD_SIZE * 2 {
+                return Err(Error::PayloadTooLarge {
+                    origin: "--payload".to_string(),
+                    limit: MAX_PAYLOAD_SIZE,
+                });
+            }
+            let bytes = decode(payload).context("--payload is not valid hex")?;
+            Ok(String::from_utf8(bytes).context("--payload is not valid utf-8")?)
+        } else if let Some(path) = &self.payload_file {
+            let file = File::open(path)
+                .with_context(|| format!("cannot open payload file {}", path.display()))?;
+            read_limited(file, &format!("payload file {}", path.display()))
+        } else if self.stdin {
+            read_limited(io::stdin().lock(), "stdin")
+        } else {
+            Err(Error::MissingPayload)
+        }
+    }
+}
+
+/// Reads `reader` to the end as UTF-8, failing once it exceeds
+/// [`MAX_PAYLOAD_SIZE`]. `origin` names it in errors.
+pub fn read_limited<R>(reader: R, origin: &str) -> Result<String>
+where
+    R: Read,
+{
+    let mut bytes = Vec::new();
+    reader
+        .take(MAX_PAYLOAD_SIZE as u64 + 1)
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("cannot read {origin}"))?;
+    if bytes.len() > MAX_PAYLOAD_SIZE {
+        return Err(Error::PayloadTooLarge {
+            origin: origin.to_string(),
+            limit: MAX_PAYLOAD_SIZE,
+        });
+    }
+
+    Ok(String::from_utf8(bytes).with_context(|| format!("{origin} is not valid utf-8"))?)
+}
+
+/// How a command prints its result. Commands the web UI reads print JSON
+/// either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Text,
+    Json,
+    Quiet,
+}
+
+impl Cli {
+    pub const fn output(&self) -> Output {
+        if self.quiet {
+            Output::Quiet
+        } else if self.json {
+            Output::Json
+        } else {
+            Output::Text
+        }
+    }
+}
+
+impl Output {
+    /// Prints `value` as JSON unless quiet.
+    pub fn json<T>(self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        if self != Self::Quiet {
+            println!("{}", serde_json::to_string(value)?);
+        }
+        Ok(())
+    }
+
+    /// Prints each item on its own line, or all of them as a JSON array.
+    pub fn list<T>(self, items: &[T]) -> Result<()>
+    where
+        T: Serialize + Display,
+    {
+        match self {
+            Self::Text => {
+                for item in items {
+                    println!("{item}");
+                }
+            }
+            Self::Json => println!("{}", serde_json::to_string_pretty(items)?),
+            Self::Quiet => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/unit/cli.rs"]
+mod tests;
@@ -0,0 +1,200 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use parking_lot::{Mutex, const_mutex};
+use regex_lite::Regex;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::{errors::ParseError, ignore::value_glob_to_regex, scanner::read_prop, utils::ksucalls};
+
+/// Partitions whose `build.prop` feeds `prop` conditions, in lookup order.
+const PROP_PARTITIONS: [&str; 5] = ["system", "system_ext", "product", "vendor", "odm"];
+
+/// Where a partition keeps its `build.prop`; `system_ext`, `product` and `odm`
+/// use `etc/`.
+const PROP_FILES: [&str; 2] = ["build.prop", "etc/build.prop"];
+
+const ROOT_MANAGERS: [&str; 3] = ["ksu", "apatch", "magisk"];
+
+static FACTS: OnceLock<Facts> = OnceLock::new();
+
+/// Compiled globs of `prop` and `kernel` conditions, `None` when invalid.
+static GLOBS: Mutex<BTreeMap<String, Option<Regex>>> = const_mutex(BTreeMap::new());
+
+/// The test of an `if` line in the custom list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    /// The property is set, or its value matches a glob.
+    Prop {
+        key: String,
+        pattern: Option<String>,
+    },
+    /// The kernel release matches a glob.
+    Kernel {
+        pattern: String,
+    },
+    Exists {
+        path: String,
+    },
+    Root {
+        manager: String,
+    },
+    Not {
+        condition: Box<Self>,
+    },
+}
+
+/// What conditions are evaluated against, read once per run.
+#[derive(Debug, Default)]
+pub struct Facts {
+    /// Where the device tree is rooted, `/` outside of tests.
+    root: PathBuf,
+    props: FxHashMap<String, String>,
+    kernel: Option<String>,
+    root_manager: Option<&'static str>,
+}
+
+fn glob_match(glob: &str, value: &str) -> bool {
+    GLOBS
+        .lock()
+        .entry(glob.to_string())
+        .or_insert_with(|| Regex::new(&value_glob_to_regex(glob)).ok())
+        .as_ref()
+        .is_some_and(|re| re.is_match(value))
+}
+
+fn detect_root_manager() -> Option<&'static str> {
+    if env::var_os("APATCH").is_some() {
+        Some("apatch")
+    } else if env::var_os("KSU").is_some() || ksucalls::version().is_some() {
+        Some("ksu")
+    } else if env::var_os("MAGISK_VER").is_some() || Path::new("/data/adb/magisk").exists() {
+        Some("magisk")
+    } else {
+        None
+    }
+}
+
+impl Facts {
+    /// Reads the `build.prop` files of the partitions and the kernel release
+    /// under `root`.
+    pub fn load<P>(root: P, root_manager: Option<&'static str>) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref();
+        let mut props = FxHashMap::default();
+        let files = PROP_PARTITIONS
+            .iter()
+            .flat_map(|partition| PROP_FILES.map(|file| root.join(partition).join(file)));
+        for file in files {
+            let Ok(partition_props) = read_prop(file) else {
+                continue;
+            };
+            for (key, value) in partition_props {
+                // the first partition to define a property wins
+                props.entry(key).or_insert(value);
+            }
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            props,
+            kernel: fs::read_to_string(root.join("proc/sys/kernel/osrelease"))
+                .ok()
+                .map(|it| it.trim().to_string()),
+            root_manager,
+        }
+    }
+
+    /// The facts of the running device.
+    pub fn detect() -> &'static Self {
+        FACTS.get_or_init(|| Self::load("/", detect_root_manager()))
+    }
+}
+
+impl Condition {
+    /// Parses the words after `if`, each with the column it starts at.
+    pub fn parse(words: &[(usize, String)], end: usize) -> Result<Self, (usize, ParseError)> {
+        let Some(((column, name), rest)) = words.split_first() else {
+            return Err((
+                end,
+                ParseError::MissingArgument {
+                    command: "if".to_string(),
+                    argument: "condition",
+                },
+            ));
+        };
+
+        let argument = |index: usize, argument: &'static str| {
+            rest.get(index)
+                .map(|(_, word)| word.clone())
+                .filter(|word| !word.is_empty())
+                .ok_or_else(|| {
+                    (
+                        rest.get(index).map_or(end, |it| it.0),
+                        ParseError::MissingArgument {
+                            command: name.clone(),
+                            argument,
+                        },
+                    )
+                })
+        };
+
+        Ok(match name.as_str() {
+            "not" | "!" => Self::Not {
+                condition: Box::new(Self::parse(rest, end)?),
+            },
+            "prop" => Self::Prop {
+                key: argument(0, "key")?,
+                pattern: argument(1, "pattern").ok(),
+            },
+            "kernel" => Self::Kernel {
+                pattern: argument(0, "pattern")?,
+            },
+            "exists" => Self::Exists {
+                path: argument(0, "path")?,
+            },
+            "root" => {
+                let manager = argument(0, "manager")?;
+                if !ROOT_MANAGERS.contains(&manager.as_str()) {
+                    return Err((rest[0].0, ParseError::UnknownRootManager { name: manager }));
+                }
+                Self::Root { manager }
+            }
+            _ => {
+                return Err((*column, ParseError::UnknownCondition { name: name.clone() }));
+            }
+        })
+    }
+
+    pub fn eval(&self, facts: &Facts) -> bool {
+        match self {
+            Self::Prop { key, pattern } => facts.props.get(key).is_some_and(|value| {
+                pattern
+                    .as_ref()
+                    .map_or(!value.is_empty(), |pattern| glob_match(pattern, value))
+            }),
+            Self::Kernel { pattern } => facts
+                .kernel
+                .as_ref()
+                .is_some_and(|kernel| glob_match(pattern, kernel)),
+            Self::Exists { path } => facts.root.join(path.trim_start_matches('/')).exists(),
+            Self::Root { manager } => facts.root_manager == Some(manager.as_str()),
+            Self::Not { condition } => !condition.eval(facts),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/unit/condition.rs"]
+mod tests;
@@ -1,17 +1,21 @@
 // Copyright (C) 2026 meta-magic_mount-rs developers
 // SPDX-License-Identifier: GPL-v3
 
-use std::{fmt, fs, path::Path};
+use std::{fmt, fs, io, path::Path};
 
 use anyhow::Context;
-use hex::decode;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::json;
+use toml_edit::{Array, DocumentMut, Item};
 
 use crate::{
-    defs,
-    errors::{Error, Result},
-    parser::{COMMAND_LIST, MountType, parser_custom},
+    cli::{Output, PayloadArgs},
+    errors::{FieldError, Result},
+    parser::{
+        COMMAND_LIST, Command, Line, MountType, ParseReport, Statement, Variables, parse_line,
+        quote_path,
+    },
+    paths::Paths,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,41 +24,158 @@ pub struct ApiCustomMount {
     pub target: String,
 }
 
+/// The version of the `ApiConfig` shape. Bumped whenever a field is renamed,
+/// removed or changes meaning; adding a field keeps it.
+pub const CONFIG_SCHEMA: u32 = 1;
+
+/// The config as `config.get` of the `api` command returns it.
 #[derive(Debug, Serialize)]
 pub struct ApiConfig {
+    pub moduledir: String,
+    pub mountsource: String,
+    pub partitions: Vec<String>,
+    pub umount: bool,
+    pub priority: Vec<String>,
+    pub ignore_list: Vec<String>,
+    pub custom_mounts: Vec<ApiCustomMount>,
+}
+
+/// The params of `config.set`; fields left out keep their value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfigPayload {
+    pub mountsource: Option<String>,
+    pub partitions: Option<Vec<String>>,
+    pub umount: Option<bool>,
+    pub priority: Option<Vec<String>>,
+    pub ignore_list: Option<Vec<String>>,
+    pub custom_mounts: Option<Vec<ApiCustomMount>>,
+}
+
+/// The unversioned JSON of `show-config`, kept for the web UI and older
+/// scripts: camelCase lists and `disable_umount` next to `umount`.
+#[derive(Debug, Serialize)]
+pub struct LegacyApiConfig {
     pub moduledir: String,
     pub mountsource: String,
     pub partitions: Vec<String>,
     pub umount: bool,
     pub disable_umount: bool,
+    pub priority: Vec<String>,
     #[serde(rename = "ignoreList")]
     pub ignore_list: Vec<String>,
     #[serde(rename = "customMounts")]
     pub custom_mounts: Vec<ApiCustomMount>,
 }
 
+/// The unversioned payload of `save-config`, which takes either spelling of
+/// the lists and `disable_umount` when `umount` is missing.
 #[derive(Debug, Deserialize)]
-pub struct ApiConfigPayload {
+pub struct LegacyApiConfigPayload {
     pub mountsource: Option<String>,
     pub partitions: Option<Vec<String>>,
     pub umount: Option<bool>,
     pub disable_umount: Option<bool>,
+    pub priority: Option<Vec<String>>,
     #[serde(rename = "ignoreList", alias = "ignore_list")]
     pub ignore_list: Option<Vec<String>>,
     #[serde(rename = "customMounts", alias = "custom_mounts")]
     pub custom_mounts: Option<Vec<ApiCustomMount>>,
 }
 
+impl From<ApiConfig> for LegacyApiConfig {
+    fn from(config: ApiConfig) -> Self {
+        Self {
+            moduledir: config.moduledir,
+            mountsource: config.mountsource,
+            partitions: config.partitions,
+            umount: config.umount,
+            disable_umount: !config.umount,
+            priority: config.priority,
+            ignore_list: config.ignore_list,
+            custom_mounts: config.custom_mounts,
+        }
+    }
+}
+
+impl From<LegacyApiConfigPayload> for ApiConfigPayload {
+    fn from(payload: LegacyApiConfigPayload) -> Self {
+        Self {
+            mountsource: payload.mountsource,
+            partitions: payload.partitions,
+            umount: payload
+                .umount
+                .or_else(|| payload.disable_umount.map(|it| !it)),
+            priority: payload.priority,
+            ignore_list: payload.ignore_list,
+            custom_mounts: payload.custom_mounts,
+        }
+    }
+}
+
+/// The keys `Config` reads from `config.toml`; others are kept but ignored.
+pub const CONFIG_KEYS: [&str; 4] = ["mountsource", "partitions", "umount", "priority"];
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
-    #[serde(default = "default_mountsource")]
     pub mountsource: String,
     pub partitions: Vec<String>,
     pub umount: bool,
+    /// Module ids merged first, so they win paths other modules also provide.
+    pub priority: Vec<String>,
+}
+
+/// The value of `key` in `table`, or `default` when it is missing or invalid.
+fn field<T>(table: &toml::Table, key: &'static str, default: T, errors: &mut Vec<FieldError>) -> T
+where
+    T: DeserializeOwned,
+{
+    match table.get(key).cloned().map(toml::Value::try_into) {
+        Some(Ok(value)) => value,
+        Some(Err(e)) => {
+            errors.push(FieldError {
+                key,
+                message: e.message().to_string(),
+            });
+            default
+        }
+        None => default,
+    }
+}
+
+/// Sets `key` in `document` unless `current` already holds `value` there.
+/// A replaced value keeps the comments around it.
+fn set_field<T, F>(
+    document: &mut DocumentMut,
+    current: &toml::Table,
+    key: &str,
+    value: &T,
+    to_toml: F,
+) where
+    T: DeserializeOwned + PartialEq,
+    F: FnOnce(&T) -> toml_edit::Value,
+{
+    let unchanged = current
+        .get(key)
+        .cloned()
+        .and_then(|old| old.try_into::<T>().ok())
+        .is_some_and(|old| old == *value);
+    if unchanged {
+        return;
+    }
+
+    let mut new = to_toml(value);
+    if let Some(old) = document.get_mut(key).and_then(Item::as_value_mut) {
+        *new.decor_mut() = old.decor().clone();
+        *old = new;
+    } else {
+        document.insert(key, Item::Value(new));
+    }
 }
 
-fn default_mountsource() -> String {
-    String::from("KSU")
+fn string_array(values: &[String]) -> toml_edit::Value {
+    toml_edit::Value::Array(values.iter().map(String::as_str).collect::<Array>())
 }
 
 impl fmt::Display for Config {
@@ -69,9 +190,10 @@ impl fmt::Display for Config {
 impl Default for Config {
     fn default() -> Self {
         Self {
-            mountsource: default_mountsource(),
+            mountsource: String::from("KSU"),
             partitions: Vec::new(),
             umount: false,
+            priority: Vec::new(),
         }
     }
 }
@@ -85,20 +207,73 @@ impl Config {
         self.umount = enabled;
     }
 
-    pub fn load<P>(path: P) -> Result<Self>
+    /// The config in `table`, with the default for every key that is missing
+    /// or has a value of the wrong type. The latter are returned as errors.
+    pub fn from_table(table: &toml::Table) -> (Self, Vec<FieldError>) {
+        let default = Self::default();
+        let mut errors = Vec::new();
+        let config = Self {
+            mountsource: field(table, "mountsource", default.mountsource, &mut errors),
+            partitions: field(table, "partitions", default.partitions, &mut errors),
+            umount: field(table, "umount", default.umount, &mut errors),
+            priority: field(table, "priority", default.priority, &mut errors),
+        };
+
+        (config, errors)
+    }
+
+    fn read_table<P>(path: P) -> Result<toml::Table>
     where
         P: AsRef<Path>,
     {
         let content = fs::read_to_string(path).context("failed to read config file")?;
+        Ok(toml::from_str(&content).context("config file is not valid toml")?)
+    }
 
-        let config: Self = toml::from_str(&content).unwrap_or_else(|e| {
-            log::error!("Failed to deserialize config to toml: {e}");
-            Self::default()
-        });
+    /// Loads `path`, falling back to the default only for invalid keys.
+    /// Fails when the file cannot be read or is not valid TOML.
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let (config, errors) = Self::from_table(&Self::read_table(path)?);
+        for error in errors {
+            log::error!("{error}, using the default");
+        }
 
         Ok(config)
     }
 
+    /// The config a boot runs with: like [`Self::load`], but a file that
+    /// cannot be read or parsed gives the default instead of failing. The
+    /// problems are returned for the boot report; a missing file is none.
+    pub fn load_for_boot<P>(path: P) -> (Self, Vec<anyhow::Error>)
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if !path.exists() {
+            return (Self::default(), Vec::new());
+        }
+
+        match Self::read_table(path) {
+            Ok(table) => {
+                let (config, errors) = Self::from_table(&table);
+                let errors = errors
+                    .into_iter()
+                    .inspect(|error| log::error!("{error}, using the default"))
+                    .map(anyhow::Error::from)
+                    .collect();
+                (config, errors)
+            }
+            Err(e) => {
+                let e = anyhow::Error::from(e);
+                log::error!("{e:#}, using the default config");
+                (Self::default(), vec![e])
+            }
+        }
+    }
+
     pub fn load_or_default<P>(path: P) -> Self
     where
         P: AsRef<Path>,
@@ -112,45 +287,103 @@ impl Config {
         }
     }
 
+    /// Writes the config into `path` in place: comments, formatting and keys
+    /// this version does not know are kept, and unchanged values left alone.
     fn save<P>(&self, path: P) -> Result<()>
     where
         P: AsRef<Path>,
     {
-        let content = toml::to_string_pretty(self).context("failed to serialize config to toml")?;
+        let content = match fs::read_to_string(path.as_ref()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => Err(e).context("failed to read config file")?,
+        };
+        let mut document: DocumentMut = content
+            .parse()
+            .context("config file is not valid toml, fix it or run gen-config")?;
+        let current: toml::Table = toml::from_str(&content).unwrap_or_default();
+
+        set_field(
+            &mut document,
+            &current,
+            "mountsource",
+            &self.mountsource,
+            |it| it.as_str().into(),
+        );
+        set_field(
+            &mut document,
+            &current,
+            "partitions",
+            &self.partitions,
+            |it| string_array(it),
+        );
+        set_field(&mut document, &current, "umount", &self.umount, |it| {
+            (*it).into()
+        });
+        set_field(&mut document, &current, "priority", &self.priority, |it| {
+            string_array(it)
+        });
 
         if let Some(parent) = path.as_ref().parent() {
             fs::create_dir_all(parent).context("failed to create config directory")?;
         }
 
-        fs::write(path, content).context("failed to write config file")?;
+        fs::write(path, document.to_string()).context("failed to write config file")?;
         Ok(())
     }
 
-    fn read_custom_lists<P>(path: P) -> (Vec<String>, Vec<ApiCustomMount>)
+    /// The ignore list and custom mounts `write_custom_list` manages in `path`.
+    /// Commands from other files, in `if` blocks or using variables are kept
+    /// as written instead.
+    fn managed_lists<'a, I>(path: &Path, commands: I) -> (Vec<String>, Vec<ApiCustomMount>)
     where
-        P: AsRef<Path>,
+        I: IntoIterator<Item = &'a Command>,
     {
-        parser_custom(path).into_iter().fold(
-            (Vec::new(), Vec::new()),
-            |(mut ignore_list, mut custom_mounts), command| {
-                match command {
-                    MountType::Ignore { source } => ignore_list.push(source),
-                    MountType::Mount { source, target } => {
-                        custom_mounts.push(ApiCustomMount { source, target });
+        commands
+            .into_iter()
+            .filter(|command| {
+                command.span.file == path && !command.conditional && !command.expanded
+            })
+            .fold(
+                (Vec::new(), Vec::new()),
+                |(mut ignore_list, mut custom_mounts), command| {
+                    match command.kind.clone() {
+                        MountType::Ignore { source } => ignore_list.push(source),
+                        MountType::Mount {
+                            source,
+                            target,
+                            options,
+                        } if options.is_default() => {
+                            custom_mounts.push(ApiCustomMount { source, target });
+                        }
+                        // kept as written by `write_custom_list`
+                        MountType::Mount { .. }
+                        | MountType::IgnoreRe { .. }
+                        | MountType::IgnoreTarget { .. }
+                        | MountType::Hide { .. }
+                        | MountType::Replace { .. }
+                        | MountType::Symlink { .. }
+                        | MountType::Mkdir { .. }
+                        | MountType::Tmpfs { .. } => {}
                     }
-                }
 
-                (ignore_list, custom_mounts)
-            },
+                    (ignore_list, custom_mounts)
+                },
+            )
+    }
+
+    fn read_custom_lists<P>(path: P) -> (Vec<String>, Vec<ApiCustomMount>)
+    where
+        P: AsRef<Path>,
+    {
+        Self::managed_lists(
+            path.as_ref(),
+            &ParseReport::from_file(path.as_ref()).commands,
         )
     }
 
     fn format_custom_path(path: &str) -> String {
-        if path.contains(char::is_whitespace) {
-            format!("\"{path}\"")
-        } else {
-            path.to_string()
-        }
+        quote_path(path)
     }
 
     fn write_custom_list<P>(
@@ -165,13 +398,54 @@ impl Config {
             fs::create_dir_all(parent).context("failed to create custom list directory")?;
         }
 
-        let mut lines: Vec<String> = ignore_list
-            .iter()
-            .map(|source| format!("ignore {}", Self::format_custom_path(source)))
+        // comments, includes, `ignore-re` lines, binds with options, lines using
+        // variables and anything inside `if` blocks are not managed here, keep them
+        let mut depth = 0usize;
+        let variables = Variables::default();
+        let mut lines: Vec<String> = fs::read_to_string(path.as_ref())
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| match parse_line(line.trim(), &variables) {
+                Ok(Line {
+                    statement: Statement::If(_),
+                    ..
+                }) => {
+                    depth += 1;
+                    true
+                }
+                Ok(Line {
+                    statement: Statement::EndIf,
+                    ..
+                }) => {
+                    depth = depth.saturating_sub(1);
+                    true
+                }
+                Ok(Line {
+                    statement: Statement::Mount(MountType::Ignore { .. }),
+                    expanded,
+                }) => depth > 0 || expanded,
+                Ok(Line {
+                    statement: Statement::Mount(MountType::Mount { options, .. }),
+                    expanded,
+                }) => depth > 0 || expanded || !options.is_default(),
+                _ => true,
+            })
+            .map(ToString::to_string)
             .collect();
+        lines.extend(
+            ignore_list
+                .iter()
+                .map(|source| format!("ignore {}", Self::format_custom_path(source))),
+        );
         lines.extend(custom_mounts.iter().map(|mount| {
+            // a source that looks like an option goes after the end of them
+            let separator = if mount.source.starts_with("--") {
+                "-- "
+            } else {
+                ""
+            };
             format!(
-                "bind {} {}",
+                "bind {separator}{} {}",
                 Self::format_custom_path(&mount.source),
                 Self::format_custom_path(&mount.target)
             )
@@ -186,15 +460,23 @@ impl Config {
         Ok(())
     }
 
-    fn into_api(self, ignore_list: Vec<String>, custom_mounts: Vec<ApiCustomMount>) -> ApiConfig {
-        let umount_enabled = self.umount_enabled();
+    fn into_api(
+        self,
+        module_dir: &Path,
+        ignore_list: Vec<String>,
+        custom_mounts: Vec<ApiCustomMount>,
+    ) -> ApiConfig {
+        let umount = self.umount_enabled();
 
         ApiConfig {
-            moduledir: defs::MODULE_PATH.trim_end_matches('/').to_string(),
+            moduledir: module_dir
+                .to_string_lossy()
+                .trim_end_matches('/')
+                .to_string(),
             mountsource: self.mountsource,
             partitions: self.partitions,
-            umount: umount_enabled,
-            disable_umount: !umount_enabled,
+            umount,
+            priority: self.priority,
             ignore_list,
             custom_mounts,
         }
@@ -209,83 +491,77 @@ impl Config {
             self.partitions = partitions;
         }
 
+        if let Some(priority) = payload.priority {
+            self.priority = priority;
+        }
+
         if let Some(umount) = payload.umount {
             self.set_umount_enabled(umount);
-        } else if let Some(disable_umount) = payload.disable_umount {
-            self.set_umount_enabled(!disable_umount);
         }
     }
 }
 
-pub fn parse_payload_arg(args: &[String]) -> Result<&str> {
-    let payload = args
-        .windows(2)
-        .find_map(|window| (window[0] == "--payload").then_some(window[1].as_str()))
-        .ok_or_else(|| Error::MissingArgment)?;
-
-    Ok(payload)
-}
-
-pub fn handle_show_config() -> Result<()> {
-    let config = Config::load_or_default(defs::CONFIG_FILE);
+/// The config with the managed parts of the custom list, as the web UI sees it.
+pub fn api_config(paths: &Paths) -> ApiConfig {
+    let config = Config::load_or_default(&paths.config_file);
+    let custom_list = paths.custom_list();
     let (ignore_list, custom_mounts) = COMMAND_LIST.get().map_or_else(
-        || Config::read_custom_lists(defs::CUSTOM_LIST_PATH),
-        |commands| {
-            commands.iter().cloned().fold(
-                (Vec::new(), Vec::new()),
-                |(mut ignore_list, mut custom_mounts), command| {
-                    match command {
-                        MountType::Ignore { source } => ignore_list.push(source),
-                        MountType::Mount { source, target } => {
-                            custom_mounts.push(ApiCustomMount { source, target });
-                        }
-                    }
-
-                    (ignore_list, custom_mounts)
-                },
-            )
-        },
+        || Config::read_custom_lists(&custom_list),
+        |commands| Config::managed_lists(&custom_list, commands),
     );
 
-    println!(
-        "{}",
-        serde_json::to_string(&config.into_api(ignore_list, custom_mounts))?
-    );
-    Ok(())
+    config.into_api(&paths.module_dir, ignore_list, custom_mounts)
 }
 
-pub fn handle_save_config(args: &[String]) -> Result<()> {
-    let payload_hex = parse_payload_arg(args)?;
-    let payload_json =
-        String::from_utf8(decode(payload_hex)?).context("decoded payload is not valid utf-8")?;
-    let payload: ApiConfigPayload =
-        serde_json::from_str(&payload_json).context("failed to parse config payload json")?;
-
+/// Applies `payload` to the config and, when it has them, the managed custom list.
+pub fn save_api_config(paths: &Paths, payload: ApiConfigPayload) -> Result<()> {
     let ignore_list = payload.ignore_list.clone();
     let custom_mounts = payload.custom_mounts.clone();
-    let mut config = Config::load_or_default(defs::CONFIG_FILE);
+    let mut config = Config::load_or_default(&paths.config_file);
     config.apply_api_payload(payload);
-    config.save(defs::CONFIG_FILE)?;
+    config.save(&paths.config_file)?;
     if ignore_list.is_some() || custom_mounts.is_some() {
         let (current_ignore_list, current_custom_mounts) =
-            Config::read_custom_lists(defs::CUSTOM_LIST_PATH);
+            Config::read_custom_lists(paths.custom_list());
         let ignore_list = ignore_list.unwrap_or(current_ignore_list);
         let custom_mounts = custom_mounts.unwrap_or(current_custom_mounts);
 
-        Config::write_custom_list(defs::CUSTOM_LIST_PATH, &ignore_list, &custom_mounts)?;
+        Config::write_custom_list(paths.custom_list(), &ignore_list, &custom_mounts)?;
     }
 
-    println!("{}", json!({ "ok": true }));
     Ok(())
 }
 
-pub fn handle_gen_config() -> Result<()> {
-    let config = Config::default();
-    config.save(defs::CONFIG_FILE)?;
-    Config::write_custom_list(defs::CUSTOM_LIST_PATH, &[], &[])?;
-    println!("{}", json!({ "ok": true }));
-    Ok(())
+/// Writes the default config and an empty custom list, replacing whatever
+/// was there.
+pub fn reset_config(paths: &Paths) -> Result<()> {
+    match fs::remove_file(&paths.config_file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(e).context("failed to remove config file")?;
+        }
+        _ => {}
+    }
+    Config::default().save(&paths.config_file)?;
+    Config::write_custom_list(paths.custom_list(), &[], &[])
+}
+
+pub fn handle_show_config(paths: &Paths, output: Output) -> Result<()> {
+    output.json(&LegacyApiConfig::from(api_config(paths)))
 }
+
+pub fn handle_save_config(paths: &Paths, payload: &PayloadArgs, output: Output) -> Result<()> {
+    let payload: LegacyApiConfigPayload =
+        serde_json::from_str(&payload.read()?).context("failed to parse config payload json")?;
+
+    save_api_config(paths, payload.into())?;
+    output.json(&json!({ "ok": true }))
+}
+
+pub fn handle_gen_config(paths: &Paths, output: Output) -> Result<()> {
+    reset_config(paths)?;
+    output.json(&json!({ "ok": true }))
+}
+
 #[cfg(test)]
 #[path = "../tests/unit/config.rs"]
 mod tests;
@@ -5,15 +5,20 @@ use const_format::concatcp;
 
 const ADB: &str = "/data/adb";
 pub const MODULE_PATH: &str = concatcp!(ADB, "/modules");
-const MAGIC_MOUNT: &str = concatcp!(ADB, "/magic_mount");
-pub const CUSTOM_LIST_PATH: &str = concatcp!(MAGIC_MOUNT, "/custom");
+pub const CONFIG_DIR: &str = concatcp!(ADB, "/magic_mount");
+pub const WORK_ROOT: &str = "/debug_ramdisk";
 pub const SELINUX_XATTR: &str = "security.selinux";
 pub const DISABLE_FILE_NAME: &str = "disable";
 pub const REMOVE_FILE_NAME: &str = "remove";
 pub const SKIP_MOUNT_FILE_NAME: &str = "skip_mount";
+pub const MODULE_LIST_FILE_NAME: &str = "magic_mount.list";
 pub const REPLACE_DIR_XATTR: &str = "trusted.overlay.opaque";
 pub const REPLACE_DIR_FILE_NAME: &str = ".replace";
-pub const CONFIG_FILE: &str = concatcp!(MAGIC_MOUNT, "/config.toml");
-pub const MODULE_PROP: &str = concatcp!(MODULE_PATH, "/", env!("MODULE_ID"), "/module.prop");
-pub const SCANNED_LIST: &str = concatcp!(MAGIC_MOUNT, "/scan.ret");
-pub const UMOUNT_LIST: &str = concatcp!(MAGIC_MOUNT, "/umount.list");
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+pub const CUSTOM_LIST_FILE_NAME: &str = "custom";
+pub const SCANNED_LIST_FILE_NAME: &str = "scan.ret";
+pub const MOUNT_STATUS_FILE_NAME: &str = "status.json";
+pub const LAST_RUN_REPORT_FILE_NAME: &str = "last_run.json";
+pub const MODULE_RULES_DIR_NAME: &str = "rules";
+pub const UMOUNT_LIST_FILE_NAME: &str = "umount.list";
+pub const BIND_WORK_DIR_PREFIX: &str = "bind";
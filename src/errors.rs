@@ -1,10 +1,26 @@
 // Copyright (C) 2026 meta-magic_mount-rs developers
 // SPDX-License-Identifier: GPL-v3
 
+use serde::Serialize;
 use thiserror::Error as ThisError;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// The `code` of an `api` error response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request is not valid JSON or misses `method`.
+    InvalidRequest,
+    /// The request asks for an API version this build does not speak.
+    UnsupportedVersion,
+    UnknownMethod,
+    /// `params` does not fit the method.
+    InvalidParams,
+    /// The method ran and failed.
+    Failed,
+}
+
 #[derive(ThisError, Debug)]
 pub enum Error {
     #[error("cannot mount root symlink {path:?}!")]
@@ -17,8 +33,14 @@ pub enum Error {
     RegularDirectory { path: String },
     #[error("Invalid module ID: '{module_id:?}'. Must match /^[a-zA-Z][a-zA-Z0-9._-]+$/")]
     InvalidModuleID { module_id: String },
-    #[error("missing required --payload argument")]
-    MissingArgment,
+    #[error("no payload given, pass --payload, --payload-file or --stdin")]
+    MissingPayload,
+    #[error("{origin} is larger than the {limit} byte payload limit")]
+    PayloadTooLarge { origin: String, limit: usize },
+    #[error("api request failed ({code:?}): {message}")]
+    Api { code: ErrorCode, message: String },
+    #[error("validation failed with {errors} error(s)")]
+    Validation { errors: usize },
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
@@ -34,3 +56,54 @@ pub enum Error {
     #[error(transparent)]
     Hex(#[from] hex::FromHexError),
 }
+
+/// A config key whose value has the wrong type; the default is used instead.
+#[derive(ThisError, Debug, Clone, PartialEq, Eq)]
+#[error("invalid {key}: {message}")]
+pub struct FieldError {
+    pub key: &'static str,
+    pub message: String,
+}
+
+/// Why a line of the custom list was rejected.
+#[derive(ThisError, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParseError {
+    #[error("unknown command {verb:?}")]
+    UnknownCommand { verb: String },
+    #[error("{command} is missing its {argument}")]
+    MissingArgument {
+        command: String,
+        argument: &'static str,
+    },
+    #[error("mixed quotes in {token}")]
+    MixedQuotes { token: String },
+    #[error("unbalanced quote in {token}")]
+    UnbalancedQuote { token: String },
+    #[error("cannot read included file {path}: {message}")]
+    Include { path: String, message: String },
+    #[error("{pattern} matches no files")]
+    EmptyInclude { pattern: String },
+    #[error("include cycle: {chain}")]
+    IncludeCycle { chain: String },
+    #[error("unknown condition {name:?}")]
+    UnknownCondition { name: String },
+    #[error("unknown root manager {name:?}, expected ksu, apatch or magisk")]
+    UnknownRootManager { name: String },
+    #[error("{keyword} without a matching if")]
+    UnexpectedBlock { keyword: String },
+    #[error("if without a matching endif")]
+    UnclosedIf,
+    #[error("undefined variable {name}")]
+    UndefinedVariable { name: String },
+    #[error("invalid variable name {name:?}")]
+    InvalidVariable { name: String },
+    #[error("unterminated variable in {token}")]
+    UnclosedVariable { token: String },
+    #[error("invalid {argument} {value:?} for {command}")]
+    InvalidArgument {
+        command: String,
+        argument: &'static str,
+        value: String,
+    },
+}
@@ -0,0 +1,224 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use regex_lite::Regex;
+use rustc_hash::FxHashSet;
+
+use crate::parser::{COMMAND_LIST, MountType};
+
+static MATCHER: OnceLock<IgnoreMatcher> = OnceLock::new();
+static TARGET_MATCHER: OnceLock<IgnoreMatcher> = OnceLock::new();
+
+/// Whether `pattern` uses any glob syntax, as opposed to being a literal path.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Translates a glob into an anchored regex.
+///
+/// `*` and `?` never cross a `/`, `**` matches across directories and `**/`
+/// also matches no directory at all. `[...]` is a character class, negated by
+/// a leading `!` or `^`; an unclosed `[` is a literal.
+pub fn glob_to_regex(glob: &str) -> String {
+    translate_glob(glob, true)
+}
+
+/// Translates a glob over a plain value, such as a property, into an anchored
+/// regex. Unlike paths, `*` and `?` match any character, `/` included.
+pub fn value_glob_to_regex(glob: &str) -> String {
+    translate_glob(glob, false)
+}
+
+fn translate_glob(glob: &str, path: bool) -> String {
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if !path => re.push_str(".*"),
+            '?' if !path => re.push('.'),
+            '*' if chars.next_if_eq(&'*').is_some() => {
+                if chars.next_if_eq(&'/').is_some() {
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                let mut lookahead = chars.clone();
+                let negated = lookahead.next_if(|c| matches!(c, '!' | '^')).is_some();
+                let mut class = String::new();
+                let mut closed = false;
+                for c in lookahead.by_ref() {
+                    // a `]` right after the opening bracket is part of the class
+                    if c == ']' && !class.is_empty() {
+                        closed = true;
+                        break;
+                    }
+                    class.push(c);
+                }
+
+                if closed {
+                    chars = lookahead;
+                    re.push('[');
+                    if negated {
+                        re.push('^');
+                    }
+                    for c in class.chars() {
+                        if matches!(c, '\\' | '[' | ']' | '^' | '&' | '~') {
+                            re.push('\\');
+                        }
+                        re.push(c);
+                    }
+                    re.push(']');
+                } else {
+                    re.push_str(r"\[");
+                }
+            }
+            _ => re.push_str(&regex_lite::escape(ch.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    re.push('$');
+    re
+}
+
+/// The existing paths `pattern` matches, in lexical order. Globs are matched
+/// one component at a time, so `**` does not descend into directories here.
+pub fn glob_paths(pattern: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let component = component.as_os_str();
+        let glob = component.to_str().filter(|it| is_glob(it));
+        let Some(re) = glob.and_then(|glob| Regex::new(&glob_to_regex(glob)).ok()) else {
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        };
+
+        paths = paths
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flat_map(Iterator::flatten)
+            .filter(|entry| entry.file_name().to_str().is_some_and(|it| re.is_match(it)))
+            .map(|entry| entry.path())
+            .collect();
+        paths.sort();
+    }
+
+    paths.retain(|path| path.exists());
+    paths
+}
+
+/// Decides which paths the `ignore`, `ignore-re` and `ignore-target` directives skip.
+///
+/// Literal paths are looked up in a set; globs and regexes are folded into a
+/// single alternation so each path is scanned once.
+#[derive(Debug, Default)]
+pub struct IgnoreMatcher {
+    literals: FxHashSet<String>,
+    pattern: Option<Regex>,
+}
+
+enum Pattern {
+    /// A literal path or a glob.
+    Path(String),
+    Regex(String),
+}
+
+impl IgnoreMatcher {
+    fn build<I>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = Pattern>,
+    {
+        let mut literals = FxHashSet::default();
+        let mut regexes = Vec::new();
+
+        for pattern in patterns {
+            let pattern = match pattern {
+                Pattern::Path(path) if !is_glob(&path) => {
+                    literals.insert(path);
+                    continue;
+                }
+                Pattern::Path(glob) => glob_to_regex(&glob),
+                Pattern::Regex(regex) => regex,
+            };
+
+            match Regex::new(&pattern) {
+                Ok(_) => regexes.push(format!("(?:{pattern})")),
+                Err(e) => log::warn!("invalid ignore pattern {pattern:?}, skip: {e}"),
+            }
+        }
+
+        let pattern = (!regexes.is_empty())
+            .then(|| Regex::new(&regexes.join("|")))
+            .transpose()
+            .unwrap_or_else(|e| {
+                log::warn!("failed to combine ignore patterns: {e}");
+                None
+            });
+
+        Self { literals, pattern }
+    }
+
+    /// Matches module-side paths, from `ignore` and `ignore-re`.
+    pub fn new<'a, I>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = &'a MountType>,
+    {
+        Self::build(commands.into_iter().filter_map(|command| match command {
+            MountType::Ignore { source } => Some(Pattern::Path(source.clone())),
+            MountType::IgnoreRe { pattern } => Some(Pattern::Regex(pattern.clone())),
+            _ => None,
+        }))
+    }
+
+    /// Matches system paths as `/system/...`, from `ignore-target`. Targets on
+    /// other partitions, like `/vendor/...`, are matched where modules keep
+    /// them, under `/system/vendor/...`.
+    pub fn for_targets<'a, I>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = &'a MountType>,
+    {
+        Self::build(commands.into_iter().filter_map(|command| match command {
+            MountType::IgnoreTarget { target } if Path::new(target).starts_with("/system") => {
+                Some(Pattern::Path(target.clone()))
+            }
+            MountType::IgnoreTarget { target } => Some(Pattern::Path(format!("/system{target}"))),
+            _ => None,
+        }))
+    }
+
+    /// The module-side matcher for the custom list loaded at startup.
+    pub fn global() -> &'static Self {
+        MATCHER.get_or_init(|| Self::new(COMMAND_LIST.get().unwrap().iter().map(|it| &it.kind)))
+    }
+
+    /// The target-side matcher for the custom list loaded at startup.
+    pub fn targets() -> &'static Self {
+        TARGET_MATCHER.get_or_init(|| {
+            Self::for_targets(COMMAND_LIST.get().unwrap().iter().map(|it| &it.kind))
+        })
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.literals.contains(path)
+            || self
+                .pattern
+                .as_ref()
+                .is_some_and(|pattern| pattern.is_match(path))
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/unit/ignore.rs"]
+mod tests;
@@ -2,6 +2,8 @@
 // SPDX-License-Identifier: GPL-v3
 
 pub mod node;
+pub mod plan;
+pub mod status;
 pub mod utils;
 
 use std::{
@@ -11,18 +13,20 @@ use std::{
 };
 
 use anyhow::Context;
-use rustix::mount::{
-    MountFlags, MountPropagationFlags, mount, mount_bind, mount_change, mount_move, mount_remount,
-};
+use rustix::mount::{MountFlags, MountPropagationFlags};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    config::Config,
     errors::{Error, Result},
     magic_mount::{
-        node::{Node, NodeFileType},
-        utils::{clone_symlink, collect_module_files, mount_mirror},
+        node::{Conflict, Node, NodeFileType, Synthetic},
+        utils::{clone_symlink, collect_module_files, mount_mirror, new_symlink},
     },
+    mount_backend::MountBackend,
     mount_list,
-    utils::{ensure_dir_exists, ksucalls::send_unmountable},
+    paths::Paths,
+    utils::{ensure_dir_exists, ksucalls::send_unmountable, lsetfilecon},
 };
 
 static MOUNTDED_FILES: AtomicU32 = AtomicU32::new(0);
@@ -36,6 +40,7 @@ struct MagicMount<'a> {
     has_tmpfs: bool,
     umount: bool,
     mounts: &'a mount_list::MountList,
+    backend: &'a dyn MountBackend,
 }
 
 impl<'a> MagicMount<'a> {
@@ -46,6 +51,7 @@ impl<'a> MagicMount<'a> {
         has_tmpfs: bool,
         umount: bool,
         mounts: &'a mount_list::MountList,
+        backend: &'a dyn MountBackend,
     ) -> Self
     where
         P: AsRef<Path>,
@@ -57,45 +63,89 @@ impl<'a> MagicMount<'a> {
             has_tmpfs,
             umount,
             mounts,
+            backend,
         }
     }
 
     fn do_mount(&mut self) -> Result<()> {
-        match self.node.file_type {
+        let result = match self.node.file_type {
             NodeFileType::Symlink => self.symlink(),
             NodeFileType::RegularFile => self.regular_file(),
-            NodeFileType::Directory => self.directory(),
+            NodeFileType::Directory => return self.directory(),
             NodeFileType::Whiteout => {
                 log::debug!("file {} is removed", self.path.display());
+                status::record(self.node.module_id.as_deref(), |it| it.whiteouts += 1);
                 Ok(())
             }
+        };
+
+        // directories are reported where their failure is handled, see `child_failed`
+        if let Err(e) = &result {
+            status::record(self.node.module_id.as_deref(), |it| {
+                it.errors.push(format!("{e:#}"));
+            });
+        }
+
+        result
+    }
+
+    fn child_skipped(&self, node: &Node) {
+        status::record(node.module_id.as_deref(), |it| {
+            it.skipped.push(self.path.join(&node.name));
+        });
+    }
+
+    fn child_failed(node: &Node, e: &anyhow::Error) {
+        if node.file_type == NodeFileType::Directory {
+            status::record(node.module_id.as_deref(), |it| {
+                it.errors.push(format!("{e:#}"));
+            });
         }
     }
 }
 
 impl MagicMount<'_> {
     fn symlink(&self) -> Result<()> {
-        if let Some(module_path) = &self.node.module_path {
+        if let Some(Synthetic::Symlink { link }) = &self.node.synthetic {
+            log::debug!(
+                "create custom symlink {} -> {}",
+                self.work_dir_path.display(),
+                link.display()
+            );
+            new_symlink(link, &self.work_dir_path, self.backend).with_context(|| {
+                format!(
+                    "create custom symlink {} -> {}",
+                    self.work_dir_path.display(),
+                    link.display(),
+                )
+            })?;
+        } else if let Some(module_path) = &self.node.module_path {
             log::debug!(
                 "create module symlink {} -> {}",
                 module_path.display(),
                 self.work_dir_path.display()
             );
-            clone_symlink(module_path, &self.work_dir_path).with_context(|| {
+            clone_symlink(module_path, &self.work_dir_path, self.backend).with_context(|| {
                 format!(
                     "create module symlink {} -> {}",
                     module_path.display(),
                     self.work_dir_path.display(),
                 )
             })?;
-            let mounted = MOUNTDED_SYMBOLS_FILES.load(std::sync::atomic::Ordering::Relaxed) + 1;
-            MOUNTDED_SYMBOLS_FILES.store(mounted, std::sync::atomic::Ordering::Relaxed);
-            Ok(())
+            if let Some(context) = &self.node.context {
+                log::info!("label symlink {} as {context}", self.path.display());
+                lsetfilecon(self.backend, &self.work_dir_path, context)?;
+            }
         } else {
-            Err(Error::MountRootSymlink {
+            return Err(Error::MountRootSymlink {
                 path: self.path.display().to_string(),
-            })
+            });
         }
+
+        let mounted = MOUNTDED_SYMBOLS_FILES.load(std::sync::atomic::Ordering::Relaxed) + 1;
+        MOUNTDED_SYMBOLS_FILES.store(mounted, std::sync::atomic::Ordering::Relaxed);
+        status::record(self.node.module_id.as_deref(), |it| it.symlinks += 1);
+        Ok(())
     }
 
     fn regular_file(&self) -> Result<()> {
@@ -113,6 +163,14 @@ impl MagicMount<'_> {
         }
 
         let module_path = &self.node.module_path.clone().unwrap();
+        if let Some(context) = &self.node.context {
+            log::info!(
+                "label {} as {context} for {}",
+                module_path.display(),
+                self.path.display()
+            );
+            lsetfilecon(self.backend, module_path, context)?;
+        }
 
         log::debug!(
             "mount module file {} -> {}",
@@ -120,24 +178,33 @@ impl MagicMount<'_> {
             self.work_dir_path.display()
         );
 
-        if mount_bind(module_path, target)
-            .with_context(|| {
-                format!(
-                    "mount module file {} -> {}",
-                    module_path.display(),
-                    self.work_dir_path.display(),
-                )
-            })
-            .is_ok()
-        {
-            self.mounts.record_if_final(&self.path, self.has_tmpfs);
-            if self.umount && !self.work_dir_path.starts_with("/mnt") {
-                send_unmountable(target);
+        match self.backend.bind(module_path, target).with_context(|| {
+            format!(
+                "mount module file {} -> {}",
+                module_path.display(),
+                self.work_dir_path.display(),
+            )
+        }) {
+            Ok(()) => {
+                self.mounts.record_if_final(&self.path, self.has_tmpfs);
+                if self.umount && !self.work_dir_path.starts_with("/mnt") {
+                    send_unmountable(target);
+                }
+                status::record(self.node.module_id.as_deref(), |it| it.mounted_files += 1);
+            }
+            Err(e) => {
+                log::error!("{e:#}");
+                status::record(self.node.module_id.as_deref(), |it| {
+                    it.errors.push(format!("{e:#}"));
+                });
             }
         }
 
         // we should use MS_REMOUNT | MS_BIND | MS_xxx to change mount flags
-        if let Err(e) = mount_remount(target, MountFlags::RDONLY | MountFlags::BIND, "") {
+        if let Err(e) = self
+            .backend
+            .remount(target, MountFlags::RDONLY | MountFlags::BIND)
+        {
             log::warn!("make file {} ro: {e:#?}", target.display());
         }
 
@@ -146,66 +213,44 @@ impl MagicMount<'_> {
         Ok(())
     }
 
-    #[allow(clippy::too_many_lines)]
     fn directory(&mut self) -> Result<()> {
-        let mut tmpfs = !self.has_tmpfs && self.node.replace && self.node.module_path.is_some();
-
-        if !self.has_tmpfs && !tmpfs {
-            for it in &mut self.node.children {
-                let (name, node) = it;
-                let real_path = self.path.join(name);
-                let need = match node.file_type {
-                    NodeFileType::Symlink => true,
-                    NodeFileType::Whiteout => real_path.exists(),
-                    _ => {
-                        if let Ok(metadata) = real_path.symlink_metadata() {
-                            let file_type = NodeFileType::from(metadata.file_type());
-                            file_type != node.file_type || file_type == NodeFileType::Symlink
-                        } else {
-                            // real path not exists
-                            true
-                        }
-                    }
-                };
-                if need {
-                    if self.node.module_path.is_none() {
-                        log::error!(
-                            "cannot create tmpfs on {}, ignore: {name}",
-                            self.path.display()
-                        );
-                        let ignored_files =
-                            IGNORED_FILES.load(std::sync::atomic::Ordering::Relaxed) + 1;
-                        IGNORED_FILES.store(ignored_files, std::sync::atomic::Ordering::Relaxed);
-                        node.skip = true;
-                        continue;
-                    }
-                    tmpfs = true;
-                    break;
-                }
-            }
-        }
+        let tmpfs = decide_tmpfs(&mut self.node, &self.path, self.has_tmpfs);
         let has_tmpfs = tmpfs || self.has_tmpfs;
 
+        let empty = if let Some(Synthetic::Tmpfs { source, size }) = &self.node.synthetic {
+            // the skeleton labels the root of the new tmpfs
+            fs::create_dir_all(&self.work_dir_path)?;
+            self.backend
+                .tmpfs(source, &self.work_dir_path, size.as_deref())
+                .with_context(|| format!("creating tmpfs {}", self.path.display()))?;
+            self.mounts.record_if_final(&self.path, true);
+            true
+        } else {
+            false
+        };
+
         if has_tmpfs {
-            utils::tmpfs_skeleton(&self.path, &self.work_dir_path, &self.node)?;
+            utils::tmpfs_skeleton(&self.path, &self.work_dir_path, &self.node, self.backend)?;
         }
 
         if tmpfs {
-            mount_bind(&self.work_dir_path, &self.work_dir_path).with_context(|| {
-                format!(
-                    "creating tmpfs for {} at {}",
-                    self.path.display(),
-                    self.work_dir_path.display(),
-                )
-            })?;
+            self.backend
+                .bind(&self.work_dir_path, &self.work_dir_path)
+                .with_context(|| {
+                    format!(
+                        "creating tmpfs for {} at {}",
+                        self.path.display(),
+                        self.work_dir_path.display(),
+                    )
+                })?;
         }
 
-        if self.path.exists() && !self.node.replace {
+        if self.path.exists() && !self.node.replace && !empty {
             self.mount_path(has_tmpfs)?;
         }
 
         if self.node.replace {
-            if self.node.module_path.is_none() {
+            if !self.node.tmpfs_allowed() {
                 return Err(Error::DirDeclared {
                     path: self.path.display().to_string(),
                 });
@@ -216,6 +261,7 @@ impl MagicMount<'_> {
 
         for (name, node) in &self.node.children {
             if node.skip {
+                self.child_skipped(node);
                 continue;
             }
 
@@ -227,11 +273,13 @@ impl MagicMount<'_> {
                     has_tmpfs,
                     self.umount,
                     self.mounts,
+                    self.backend,
                 )
                 .do_mount()
             }
             .with_context(|| format!("magic mount {}/{name}", self.path.display()))
             {
+                Self::child_failed(node, &e);
                 if has_tmpfs {
                     return Err(e.into());
                 }
@@ -247,24 +295,25 @@ impl MagicMount<'_> {
                 self.path.display()
             );
 
-            if let Err(e) = mount_remount(
-                &self.work_dir_path,
-                MountFlags::RDONLY | MountFlags::BIND,
-                "",
-            ) {
+            if let Err(e) = self
+                .backend
+                .remount(&self.work_dir_path, MountFlags::RDONLY | MountFlags::BIND)
+            {
                 log::warn!("make dir {} ro: {e:#?}", self.path.display());
             }
-            mount_move(&self.work_dir_path, &self.path).with_context(|| {
-                format!(
-                    "moving tmpfs {} -> {}",
-                    self.work_dir_path.display(),
-                    self.path.display()
-                )
-            })?;
+            self.backend
+                .move_mount(&self.work_dir_path, &self.path)
+                .with_context(|| {
+                    format!(
+                        "moving tmpfs {} -> {}",
+                        self.work_dir_path.display(),
+                        self.path.display()
+                    )
+                })?;
             self.mounts.commit_staged_under(&self.path);
             self.mounts.record(&self.path);
             // make private to reduce peer group count
-            if let Err(e) = mount_change(
+            if let Err(e) = self.backend.change(
                 &self.path,
                 MountPropagationFlags::PRIVATE | MountPropagationFlags::REC,
             ) {
@@ -287,6 +336,7 @@ impl MagicMount<'_> {
             let result = {
                 if let Some(node) = self.node.children.remove(&name) {
                     if node.skip {
+                        self.child_skipped(&node);
                         continue;
                     }
 
@@ -297,11 +347,13 @@ impl MagicMount<'_> {
                         has_tmpfs,
                         self.umount,
                         self.mounts,
+                        self.backend,
                     )
                     .do_mount()
                     .with_context(|| format!("magic mount {}/{name}", self.path.display()))
+                    .inspect_err(|e| Self::child_failed(&node, e))
                 } else if has_tmpfs {
-                    mount_mirror(&self.path, &self.work_dir_path, &entry)
+                    mount_mirror(&self.path, &self.work_dir_path, &entry, self.backend)
                         .with_context(|| format!("mount mirror {}/{name}", self.path.display()))
                 } else {
                     Ok(())
@@ -320,44 +372,139 @@ impl MagicMount<'_> {
     }
 }
 
-pub fn magic_mount<P>(
-    module_dir: P,
-    mount_source: &str,
-    extra_partitions: &[String],
-    umount: bool,
-    mounts: &mount_list::MountList,
-) -> Result<()>
-where
-    P: AsRef<Path>,
-{
-    if let Some(root) = collect_module_files(module_dir.as_ref(), extra_partitions)? {
+/// Whether `node` needs a tmpfs built at `path`. Children that would need one
+/// under a directory no tmpfs is allowed for are marked as skipped instead.
+fn decide_tmpfs(node: &mut Node, path: &Path, has_tmpfs: bool) -> bool {
+    let (tmpfs, skipped) = tmpfs_decision(node, path, has_tmpfs);
+
+    for name in skipped {
+        log::error!("cannot create tmpfs on {}, ignore: {name}", path.display());
+        let ignored_files = IGNORED_FILES.load(std::sync::atomic::Ordering::Relaxed) + 1;
+        IGNORED_FILES.store(ignored_files, std::sync::atomic::Ordering::Relaxed);
+        if let Some(child) = node.children.get_mut(&name) {
+            child.skip = true;
+        }
+    }
+
+    tmpfs
+}
+
+/// The decision behind `decide_tmpfs`, without logging or touching the node:
+/// whether a tmpfs is needed, and the children that have to be skipped.
+fn tmpfs_decision(node: &Node, path: &Path, has_tmpfs: bool) -> (bool, Vec<String>) {
+    if has_tmpfs {
+        return (false, Vec::new());
+    }
+    if node.replace && node.tmpfs_allowed() {
+        return (true, Vec::new());
+    }
+
+    let mut skipped = Vec::new();
+    for (name, child) in &node.children {
+        let real_path = path.join(name);
+        let need = match child.file_type {
+            // only a tmpfs can hold what the custom list creates
+            _ if child.synthetic.is_some() => true,
+            NodeFileType::Symlink => true,
+            NodeFileType::Whiteout => real_path.exists(),
+            // a missing real path needs one as well
+            _ => real_path.symlink_metadata().map_or(true, |metadata| {
+                let file_type = NodeFileType::from(metadata.file_type());
+                file_type != child.file_type || file_type == NodeFileType::Symlink
+            }),
+        };
+        if need {
+            if !node.tmpfs_allowed() {
+                skipped.push(name.clone());
+                continue;
+            }
+            return (true, Vec::new());
+        }
+    }
+
+    (false, skipped)
+}
+
+pub fn conflicts(paths: &Paths, config: &Config) -> Result<Vec<Conflict>> {
+    Ok(
+        collect_module_files(&paths.module_dir, &paths.rules_dir(), config)?
+            .map_or_else(Vec::new, |root| root.conflicts()),
+    )
+}
+
+/// Collects the merged module tree, logging every conflict found on the way.
+pub fn collect(paths: &Paths, config: &Config) -> Result<Option<Node>> {
+    let root = collect_module_files(&paths.module_dir, &paths.rules_dir(), config)?;
+
+    if let Some(root) = &root {
         log::debug!("collected: {root:?}");
-        let tmp_root = Path::new("/debug_ramdisk");
-        let tmp_dir = tmp_root.join("workdir");
+        for conflict in root.conflicts() {
+            log::warn!("module conflict: {conflict}");
+        }
+    }
+
+    Ok(root)
+}
+
+pub fn magic_mount(
+    root: Option<&Node>,
+    config: &Config,
+    paths: &Paths,
+    mounts: &mount_list::MountList,
+    backend: &dyn MountBackend,
+) -> Result<()> {
+    if let Some(root) = root {
+        let tmp_dir = paths.work_dir();
         ensure_dir_exists(&tmp_dir)?;
 
-        mount(mount_source, &tmp_dir, "tmpfs", MountFlags::empty(), None).context("mount tmp")?;
-        mount_change(
-            &tmp_dir,
-            MountPropagationFlags::PRIVATE | MountPropagationFlags::REC,
-        )
-        .context("make tmp recursively private")?;
+        backend
+            .tmpfs(&config.mountsource, &tmp_dir, None)
+            .context("mount tmp")?;
+        backend
+            .change(
+                &tmp_dir,
+                MountPropagationFlags::PRIVATE | MountPropagationFlags::REC,
+            )
+            .context("make tmp recursively private")?;
 
         MagicMount::new(
-            &root,
+            root,
             Path::new("/"),
             tmp_dir.as_path(),
             false,
-            umount,
+            config.umount,
             mounts,
+            backend,
         )
         .do_mount()?;
     } else {
         log::info!("no modules to mount, skipping!");
     }
-    let mounted_symbols = MOUNTDED_SYMBOLS_FILES.load(std::sync::atomic::Ordering::Relaxed);
-    let mounted_files = MOUNTDED_FILES.load(std::sync::atomic::Ordering::Relaxed);
-    log::info!("mounted files: {mounted_files}, mounted symlinks: {mounted_symbols}");
-    crate::utils::update_desc(mounted_files, mounted_symbols)?;
+    let MountStats {
+        mounted_files,
+        mounted_symlinks,
+        ..
+    } = stats();
+    log::info!("mounted files: {mounted_files}, mounted symlinks: {mounted_symlinks}");
+    crate::utils::update_desc(paths.module_prop(), mounted_files, mounted_symlinks)?;
     Ok(())
 }
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct MountStats {
+    pub mounted_files: u32,
+    pub mounted_symlinks: u32,
+    pub ignored_files: u32,
+}
+
+pub fn stats() -> MountStats {
+    MountStats {
+        mounted_files: MOUNTDED_FILES.load(std::sync::atomic::Ordering::Relaxed),
+        mounted_symlinks: MOUNTDED_SYMBOLS_FILES.load(std::sync::atomic::Ordering::Relaxed),
+        ignored_files: IGNORED_FILES.load(std::sync::atomic::Ordering::Relaxed),
+    }
+}
+
+#[cfg(test)]
+#[path = "../../tests/unit/magic_mount.rs"]
+mod tests;
@@ -12,8 +12,11 @@ use std::{
 use extattr::lgetxattr;
 use rustc_hash::FxHashMap;
 use rustix::path::Arg;
+use serde::Serialize;
 
-use crate::{defs, errors::Result, parser::COMMAND_LIST};
+use crate::{
+    defs, errors::Result, ignore::IgnoreMatcher, magic_mount::status, scanner::ModuleRules,
+};
 
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub enum NodeFileType {
@@ -37,6 +40,26 @@ impl From<FileType> for NodeFileType {
     }
 }
 
+/// A node the custom list creates where no module provides one.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Synthetic {
+    Symlink {
+        link: PathBuf,
+    },
+    Directory {
+        mode: u32,
+        uid: u32,
+        gid: u32,
+        /// The context of the parent directory when unset.
+        context: Option<String>,
+    },
+    /// An empty tmpfs mounted from `source`.
+    Tmpfs {
+        source: String,
+        size: Option<String>,
+    },
+}
+
 #[derive(Clone)]
 pub struct Node {
     pub name: String,
@@ -44,8 +67,37 @@ pub struct Node {
     pub children: FxHashMap<String, Self>,
     // the module that owned this node
     pub module_path: Option<PathBuf>,
+    pub module_id: Option<String>,
+    // modules that provided the same path but lost to the owner
+    pub shadowed: Vec<String>,
     pub replace: bool,
     pub skip: bool,
+    pub synthetic: Option<Synthetic>,
+    /// Changed by the custom list, so a tmpfs may be built here even though no
+    /// module provides the directory.
+    pub custom: bool,
+    /// The label from the module `contexts` rules, in place of the usual one.
+    pub context: Option<String>,
+}
+
+/// A path provided by more than one module.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: PathBuf,
+    pub module: String,
+    pub shadowed: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is provided by {}, shadowing {}",
+            self.path.display(),
+            self.module,
+            self.shadowed.join(", ")
+        )
+    }
 }
 
 impl fmt::Debug for Node {
@@ -73,12 +125,24 @@ impl Node {
         if let Some(path) = &self.module_path {
             write!(f, " [{}]", path.display())?;
         }
+        if !self.shadowed.is_empty() {
+            write!(f, " [SHADOWED {}]", self.shadowed.join(", "))?;
+        }
         if self.replace {
             write!(f, " [REPLACE]")?;
         }
         if self.skip {
             write!(f, " [SKIP]")?;
         }
+        if let Some(synthetic) = &self.synthetic {
+            write!(f, " [{synthetic:?}]")?;
+        }
+        if self.custom {
+            write!(f, " [CUSTOM]")?;
+        }
+        if let Some(context) = &self.context {
+            write!(f, " [CONTEXT {context}]")?;
+        }
         writeln!(f)?;
 
         for child in self.children.values() {
@@ -89,23 +153,75 @@ impl Node {
 }
 
 impl Node {
-    pub fn collect_module_files<P>(&mut self, module_dir: P) -> Result<bool>
+    pub fn collect_module_files<P>(
+        &mut self,
+        module_dir: P,
+        module_id: &str,
+        rules: &ModuleRules,
+    ) -> Result<bool>
     where
         P: AsRef<Path>,
     {
-        let dir = module_dir.as_ref();
+        let rel = PathBuf::from(&self.name);
+        self.collect_module_dir(module_dir.as_ref(), &rel, module_id, rules)
+    }
+
+    fn collect_module_dir(
+        &mut self,
+        dir: &Path,
+        rel: &Path,
+        module_id: &str,
+        rules: &ModuleRules,
+    ) -> Result<bool> {
         let mut has_file = false;
         for entry in dir.read_dir()?.flatten() {
             let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            let rel = rel.join(&name);
+
+            if rules.ignores(&rel) {
+                log::debug!("{} from {module_id} is ignored by rules", rel.display());
+                continue;
+            }
+
+            let target = Path::new("/").join(&rel);
+            if IgnoreMatcher::targets().is_match(&target.to_string_lossy()) {
+                log::info!(
+                    "{} from {module_id} is dropped by ignore-target",
+                    target.display()
+                );
+                status::record(Some(module_id), |it| it.ignored_targets.push(target));
+                continue;
+            }
 
             let node = match self.children.entry(name.clone()) {
-                Entry::Occupied(o) => Some(o.into_mut()),
-                Entry::Vacant(v) => Self::new_module(&name, &entry).map(|it| v.insert(it)),
+                Entry::Occupied(o) => {
+                    let node = o.into_mut();
+                    // directories merge, anything else keeps the first module's entry
+                    if node.file_type != NodeFileType::Directory || !is_dir {
+                        log::debug!(
+                            "{} from {module_id} is shadowed by {}",
+                            entry.path().display(),
+                            node.module_id.as_deref().unwrap_or_default()
+                        );
+                        node.shadowed.push(module_id.to_string());
+                        has_file = true;
+                        continue;
+                    }
+                    Some(node)
+                }
+                Entry::Vacant(v) => Self::new_module(&name, &entry, module_id).map(|it| {
+                    v.insert(Self {
+                        context: rules.context_for(&rel).map(str::to_string),
+                        ..it
+                    })
+                }),
             };
 
             if let Some(node) = node {
                 has_file |= if node.file_type == NodeFileType::Directory {
-                    node.collect_module_files(dir.join(&node.name))? || node.replace
+                    node.collect_module_dir(&dir.join(&node.name), &rel, module_id, rules)?
+                        || node.replace
                 } else {
                     true
                 }
@@ -115,6 +231,152 @@ impl Node {
         Ok(has_file)
     }
 
+    /// Finds the parent for a custom list node at `path` below this node, which
+    /// stands for `on_device`. Missing directories on the way are added for the
+    /// directories on the device. With `tmpfs` the parent is marked `custom` so it
+    /// can become a tmpfs, which this node, a partition root, never does.
+    /// Returns `None` unless the parent of `path` is a directory on the device.
+    fn custom_parent(
+        &mut self,
+        on_device: &Path,
+        path: &Path,
+        tmpfs: bool,
+    ) -> Option<(&mut Self, String, PathBuf)> {
+        let name = path.file_name()?.to_string_lossy().to_string();
+        if tmpfs && is_top_level(path) {
+            return None;
+        }
+        let mut node = self;
+        let mut real = on_device.to_path_buf();
+        for component in path.parent()? {
+            real.push(component);
+            if !real.is_dir() {
+                return None;
+            }
+
+            let name = component.to_string_lossy().to_string();
+            node = node
+                .children
+                .entry(name.clone())
+                .or_insert_with(|| Self::new_root(name));
+            if node.file_type != NodeFileType::Directory {
+                return None;
+            }
+        }
+
+        node.custom |= tmpfs;
+        real.push(&name);
+        Some((node, name, real))
+    }
+
+    /// Removes `path`, which stands for `on_device`, like a module whiteout would.
+    pub fn hide(&mut self, on_device: &Path, path: &Path) -> bool {
+        let Some((parent, name, real)) = self.custom_parent(on_device, path, true) else {
+            return false;
+        };
+        if real.symlink_metadata().is_err() {
+            return false;
+        }
+
+        if let Some(id) = parent
+            .children
+            .get(&name)
+            .and_then(|it| it.module_id.as_ref())
+        {
+            log::info!("{} from {id} is hidden by the custom list", real.display());
+        }
+        parent.children.insert(
+            name.clone(),
+            Self {
+                file_type: NodeFileType::Whiteout,
+                ..Self::new_root(name)
+            },
+        );
+        true
+    }
+
+    /// Empties the directory `path`, which stands for `on_device`, like a module
+    /// `.replace` directory would. Modules still add their files to it.
+    pub fn replace(&mut self, on_device: &Path, path: &Path) -> bool {
+        let Some((parent, name, real)) = self.custom_parent(on_device, path, false) else {
+            return false;
+        };
+        if !real.is_dir() {
+            return false;
+        }
+
+        let node = parent
+            .children
+            .entry(name.clone())
+            .or_insert_with(|| Self::new_root(name.clone()));
+        if node.file_type != NodeFileType::Directory {
+            *node = Self::new_root(name);
+        }
+        node.custom = true;
+        node.replace = true;
+        true
+    }
+
+    /// Adds `synthetic` at `path`, which stands for `on_device`, in place of
+    /// anything a module provides there. Directories keep the module files in them.
+    pub fn create(&mut self, on_device: &Path, path: &Path, synthetic: Synthetic) -> bool {
+        let Some((parent, name, real)) = self.custom_parent(on_device, path, true) else {
+            return false;
+        };
+
+        let file_type = if matches!(synthetic, Synthetic::Symlink { .. }) {
+            NodeFileType::Symlink
+        } else {
+            NodeFileType::Directory
+        };
+        let node = parent
+            .children
+            .entry(name.clone())
+            .or_insert_with(|| Self::new_root(name.clone()));
+        if node.file_type != NodeFileType::Directory || file_type != NodeFileType::Directory {
+            if let Some(id) = &node.module_id {
+                log::info!(
+                    "{} from {id} is replaced by the custom list",
+                    real.display()
+                );
+            }
+            *node = Self {
+                file_type,
+                ..Self::new_root(name)
+            };
+        }
+        node.synthetic = Some(synthetic);
+        true
+    }
+
+    /// Whether a tmpfs may be built for this directory: a module provides it,
+    /// or the custom list changes something in it.
+    pub const fn tmpfs_allowed(&self) -> bool {
+        self.module_path.is_some() || self.custom
+    }
+
+    /// Every path in this tree that more than one module provided.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        self.collect_conflicts(Path::new("/"), &mut conflicts);
+        conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+        conflicts
+    }
+
+    fn collect_conflicts(&self, parent: &Path, conflicts: &mut Vec<Conflict>) {
+        let path = parent.join(&self.name);
+        if !self.shadowed.is_empty() {
+            conflicts.push(Conflict {
+                path: path.clone(),
+                module: self.module_id.clone().unwrap_or_default(),
+                shadowed: self.shadowed.clone(),
+            });
+        }
+        for child in self.children.values() {
+            child.collect_conflicts(&path, conflicts);
+        }
+    }
+
     fn dir_is_replace<P>(path: P) -> bool
     where
         P: AsRef<Path>,
@@ -132,11 +394,8 @@ impl Node {
     where
         P: AsRef<Path>,
     {
-        let list = COMMAND_LIST.get().unwrap();
         let path = path.as_ref().to_string_lossy();
-        list.iter()
-            .any(|s| matches!(s, crate::parser::MountType::Ignore { source } if source == &path))
-            || path.ends_with(".replace")
+        IgnoreMatcher::global().is_match(&path) || path.ends_with(".replace")
     }
 
     pub fn new_root<S>(name: S) -> Self
@@ -148,12 +407,27 @@ impl Node {
             file_type: NodeFileType::Directory,
             children: FxHashMap::default(),
             module_path: None,
+            module_id: None,
+            shadowed: Vec::new(),
             replace: false,
             skip: false,
+            synthetic: None,
+            custom: false,
+            context: None,
+        }
+    }
+
+    /// A node with just a name, type and module path, for building trees in tests.
+    #[cfg(test)]
+    pub fn new_for_test(name: &str, file_type: NodeFileType, module_path: Option<PathBuf>) -> Self {
+        Self {
+            file_type,
+            module_path,
+            ..Self::new_root(name)
         }
     }
 
-    pub fn new_module<S>(name: &S, entry: &DirEntry) -> Option<Self>
+    pub fn new_module<S>(name: &S, entry: &DirEntry, module_id: &str) -> Option<Self>
     where
         S: ToString,
     {
@@ -178,11 +452,25 @@ impl Node {
                 file_type,
                 children: FxHashMap::default(),
                 module_path: Some(path),
+                module_id: Some(module_id.to_string()),
+                shadowed: Vec::new(),
                 replace,
                 skip,
+                synthetic: None,
+                custom: false,
+                context: None,
             });
         }
 
         None
     }
 }
+
+/// Whether `path`, relative to a partition root, names an entry directly in it.
+pub fn is_top_level(path: &Path) -> bool {
+    path.parent().is_none_or(|it| it.as_os_str().is_empty())
+}
+
+#[cfg(test)]
+#[path = "../../tests/unit/node.rs"]
+mod tests;
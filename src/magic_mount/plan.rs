@@ -0,0 +1,280 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+
+use crate::{
+    bind_mount,
+    config::Config,
+    errors::{Error, Result},
+    magic_mount::{
+        node::{Node, NodeFileType, Synthetic},
+        tmpfs_decision,
+        utils::collect_module_files,
+    },
+    paths::Paths,
+};
+
+/// A single step the mount engine would perform.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Bind {
+        source: PathBuf,
+        target: PathBuf,
+    },
+    Tmpfs {
+        path: PathBuf,
+        work_dir: PathBuf,
+    },
+    /// An empty tmpfs from a `tmpfs` directive.
+    Empty {
+        target: PathBuf,
+        size: Option<String>,
+    },
+    Mirror {
+        source: PathBuf,
+        target: PathBuf,
+    },
+    Symlink {
+        source: PathBuf,
+        target: PathBuf,
+    },
+    Whiteout {
+        path: PathBuf,
+    },
+    Move {
+        source: PathBuf,
+        target: PathBuf,
+    },
+    Skip {
+        path: PathBuf,
+    },
+    /// A child whose mount would fail; boot logs it and carries on.
+    Failed {
+        path: PathBuf,
+        error: String,
+    },
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bind { source, target } => {
+                write!(f, "bind     {} -> {}", source.display(), target.display())
+            }
+            Self::Tmpfs { path, work_dir } => {
+                write!(f, "tmpfs    {} (at {})", path.display(), work_dir.display())
+            }
+            Self::Empty { target, size } => {
+                write!(f, "empty    {}", target.display())?;
+                if let Some(size) = size {
+                    write!(f, " (size {size})")?;
+                }
+                Ok(())
+            }
+            Self::Mirror { source, target } => {
+                write!(f, "mirror   {} -> {}", source.display(), target.display())
+            }
+            Self::Symlink { source, target } => {
+                write!(f, "symlink  {} -> {}", source.display(), target.display())
+            }
+            Self::Whiteout { path } => write!(f, "whiteout {}", path.display()),
+            Self::Move { source, target } => {
+                write!(f, "move     {} -> {}", source.display(), target.display())
+            }
+            Self::Skip { path } => write!(f, "skip     {}", path.display()),
+            Self::Failed { path, error } => write!(f, "failed   {}: {error}", path.display()),
+        }
+    }
+}
+
+/// Everything a boot would do, computed without touching the mount table.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub magic_mount: Vec<Operation>,
+    pub bind_mount: Vec<Operation>,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "magic mount:")?;
+        for op in &self.magic_mount {
+            writeln!(f, "  {op}")?;
+        }
+        writeln!(f, "bind mount:")?;
+        for op in &self.bind_mount {
+            writeln!(f, "  {op}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Plan {
+    pub fn new(paths: &Paths, config: &Config) -> Result<Self> {
+        let mut planner = Planner::default();
+        if let Some(root) = collect_module_files(&paths.module_dir, &paths.rules_dir(), config)? {
+            planner.node(&root, Path::new("/"), &paths.work_dir(), false)?;
+        }
+
+        Ok(Self {
+            magic_mount: planner.ops,
+            bind_mount: bind_mount::plan(&paths.work_root)?,
+        })
+    }
+}
+
+/// Walks a `Node` tree the same way `MagicMount` does, recording instead of mounting.
+#[derive(Default)]
+struct Planner {
+    ops: Vec<Operation>,
+}
+
+impl Planner {
+    fn node(&mut self, node: &Node, path: &Path, work_dir: &Path, has_tmpfs: bool) -> Result<()> {
+        let path = path.join(&node.name);
+        let work_dir = work_dir.join(&node.name);
+
+        match node.file_type {
+            NodeFileType::Symlink => {
+                let Some(module_path) = (match &node.synthetic {
+                    Some(Synthetic::Symlink { link }) => Some(link),
+                    _ => node.module_path.as_ref(),
+                }) else {
+                    return Err(Error::MountRootSymlink {
+                        path: path.display().to_string(),
+                    });
+                };
+                self.ops.push(Operation::Symlink {
+                    source: module_path.clone(),
+                    target: work_dir,
+                });
+            }
+            NodeFileType::RegularFile => {
+                let Some(module_path) = &node.module_path else {
+                    return Err(Error::MountRootFile {
+                        path: path.display().to_string(),
+                    });
+                };
+                self.ops.push(Operation::Bind {
+                    source: module_path.clone(),
+                    target: if has_tmpfs { work_dir } else { path },
+                });
+            }
+            NodeFileType::Whiteout => self.ops.push(Operation::Whiteout { path }),
+            NodeFileType::Directory => {
+                self.directory(node, &path, &work_dir, has_tmpfs)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn directory(
+        &mut self,
+        node: &Node,
+        path: &Path,
+        work_dir: &Path,
+        has_tmpfs: bool,
+    ) -> Result<()> {
+        let (tmpfs, skipped) = tmpfs_decision(node, path, has_tmpfs);
+        let has_tmpfs = tmpfs || has_tmpfs;
+
+        let empty = if let Some(Synthetic::Tmpfs { size, .. }) = &node.synthetic {
+            self.ops.push(Operation::Empty {
+                target: work_dir.to_path_buf(),
+                size: size.clone(),
+            });
+            true
+        } else {
+            false
+        };
+
+        if has_tmpfs {
+            self.ops.push(Operation::Tmpfs {
+                path: path.to_path_buf(),
+                work_dir: work_dir.to_path_buf(),
+            });
+        }
+
+        let mut visited = FxHashSet::default();
+        if path.exists() && !node.replace && !empty {
+            for entry in path.read_dir()?.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some(child) = node.children.get(&name) {
+                    self.child(child, path, work_dir, has_tmpfs, skipped.contains(&name))?;
+                    visited.insert(name);
+                } else if has_tmpfs {
+                    self.ops.push(Operation::Mirror {
+                        source: path.join(&name),
+                        target: work_dir.join(&name),
+                    });
+                }
+            }
+        }
+
+        if node.replace && !node.tmpfs_allowed() {
+            return Err(Error::DirDeclared {
+                path: path.display().to_string(),
+            });
+        }
+
+        for (name, child) in &node.children {
+            if !visited.contains(name) {
+                self.child(child, path, work_dir, has_tmpfs, skipped.contains(name))?;
+            }
+        }
+
+        if tmpfs {
+            self.ops.push(Operation::Move {
+                source: work_dir.to_path_buf(),
+                target: path.to_path_buf(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn child(
+        &mut self,
+        child: &Node,
+        path: &Path,
+        work_dir: &Path,
+        has_tmpfs: bool,
+        skip: bool,
+    ) -> Result<()> {
+        if child.skip || skip {
+            self.ops.push(Operation::Skip {
+                path: path.join(&child.name),
+            });
+            return Ok(());
+        }
+
+        if let Err(e) = self
+            .node(child, path, work_dir, has_tmpfs)
+            .with_context(|| format!("magic mount {}/{}", path.display(), child.name))
+        {
+            if has_tmpfs {
+                return Err(e.into());
+            }
+
+            self.ops.push(Operation::Failed {
+                path: path.join(&child.name),
+                error: format!("{e:#}"),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "../../tests/unit/plan.rs"]
+mod tests;
@@ -0,0 +1,71 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use parking_lot::{Mutex, const_mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Result;
+
+static STATUS: Mutex<BTreeMap<String, ModuleStatus>> = const_mutex(BTreeMap::new());
+
+/// What the mount engine actually did for one module during boot.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ModuleStatus {
+    pub mounted_files: u32,
+    pub symlinks: u32,
+    pub whiteouts: u32,
+    pub skipped: Vec<PathBuf>,
+    /// System paths dropped by an `ignore-target` rule.
+    pub ignored_targets: Vec<PathBuf>,
+    pub errors: Vec<String>,
+}
+
+impl ModuleStatus {
+    pub const fn is_mounted(&self) -> bool {
+        self.mounted_files + self.symlinks + self.whiteouts > 0
+    }
+}
+
+/// Updates the status of `module_id`; nodes no module owns are not tracked.
+pub fn record<F>(module_id: Option<&str>, f: F)
+where
+    F: FnOnce(&mut ModuleStatus),
+{
+    if let Some(module_id) = module_id {
+        f(STATUS.lock().entry(module_id.to_string()).or_default());
+    }
+}
+
+#[cfg(test)]
+pub fn get(module_id: &str) -> Option<ModuleStatus> {
+    STATUS.lock().get(module_id).cloned()
+}
+
+pub fn save<P>(path: P) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let content = serde_json::to_string_pretty(&*STATUS.lock())?;
+    fs::write(path, content).context("failed to write mount status")?;
+    Ok(())
+}
+
+pub fn load<P>(path: P) -> Result<BTreeMap<String, ModuleStatus>>
+where
+    P: AsRef<Path>,
+{
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
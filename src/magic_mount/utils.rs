@@ -2,23 +2,34 @@
 // SPDX-License-Identifier: GPL-v3
 
 use std::{
+    cmp::Reverse,
     fs::{self, DirEntry, Metadata, create_dir, create_dir_all, read_link},
     os::unix::fs::{MetadataExt, symlink},
     path::{Path, PathBuf},
 };
 
-use rustix::{
-    fs::{Gid, Mode, Uid, chmod, chown},
-    mount::mount_bind,
-};
+use rustix::fs::{Gid, Mode, Uid, chmod, chown};
 
 use crate::{
+    config::Config,
     defs,
     errors::{Error, Result},
-    magic_mount::node::Node,
+    magic_mount::node::{Node, Synthetic, is_top_level},
+    mount_backend::MountBackend,
+    parser::{COMMAND_LIST, Command, MountType},
+    scanner::ModuleRules,
     utils::{lgetfilecon, lsetfilecon, validate_module_id},
 };
 
+/// Partitions mounted at `/` when a module provides them, and whether
+/// `/system/<partition>` must be a symlink for that to happen.
+pub const BUILTIN_PARTITIONS: [(&str, bool); 4] = [
+    ("vendor", true),
+    ("system_ext", true),
+    ("product", true),
+    ("odm", false),
+];
+
 fn metadata_path<P>(path: P, node: &Node) -> Result<(Metadata, PathBuf)>
 where
     P: AsRef<Path>,
@@ -35,7 +46,12 @@ where
     }
 }
 
-pub fn tmpfs_skeleton<P>(path: P, work_dir_path: P, node: &Node) -> Result<()>
+pub fn tmpfs_skeleton<P>(
+    path: P,
+    work_dir_path: P,
+    node: &Node,
+    backend: &dyn MountBackend,
+) -> Result<()>
 where
     P: AsRef<Path>,
 {
@@ -48,20 +64,50 @@ where
 
     create_dir_all(work_dir_path)?;
 
-    let (metadata, path) = metadata_path(path, node)?;
+    let (mode, uid, gid, context) = match &node.synthetic {
+        Some(Synthetic::Directory {
+            mode,
+            uid,
+            gid,
+            context,
+        }) => (*mode, *uid, *gid, context.clone()),
+        Some(Synthetic::Tmpfs { .. }) if !path.exists() => (0o755, 0, 0, None),
+        _ => {
+            let (metadata, path) = metadata_path(path, node)?;
+            let context = lgetfilecon(backend, path)?;
+            (
+                metadata.mode(),
+                metadata.uid(),
+                metadata.gid(),
+                Some(context),
+            )
+        }
+    };
+    if let Some(context) = &node.context {
+        log::info!("label {} as {context}", path.display());
+    }
+    let context = match node.context.clone().or(context) {
+        Some(context) => context,
+        None => lgetfilecon(backend, work_dir_path.parent().unwrap_or(work_dir_path))?,
+    };
 
-    chmod(work_dir_path, Mode::from_raw_mode(metadata.mode()))?;
+    chmod(work_dir_path, Mode::from_raw_mode(mode))?;
     chown(
         work_dir_path,
-        Some(Uid::from_raw(metadata.uid())),
-        Some(Gid::from_raw(metadata.gid())),
+        Some(Uid::from_raw(uid)),
+        Some(Gid::from_raw(gid)),
     )?;
-    lsetfilecon(work_dir_path, lgetfilecon(path)?.as_str())?;
+    lsetfilecon(backend, work_dir_path, &context)?;
 
     Ok(())
 }
 
-pub fn mount_mirror<P>(path: P, work_dir_path: P, entry: &DirEntry) -> Result<()>
+pub fn mount_mirror<P>(
+    path: P,
+    work_dir_path: P,
+    entry: &DirEntry,
+    backend: &dyn MountBackend,
+) -> Result<()>
 where
     P: AsRef<Path>,
 {
@@ -76,7 +122,7 @@ where
             work_dir_path.display()
         );
         fs::File::create(&work_dir_path)?;
-        mount_bind(&path, &work_dir_path)?;
+        backend.bind(&path, &work_dir_path)?;
     } else if file_type.is_dir() {
         log::debug!(
             "mount mirror dir {} -> {}",
@@ -91,9 +137,13 @@ where
             Some(Uid::from_raw(metadata.uid())),
             Some(Gid::from_raw(metadata.gid())),
         )?;
-        lsetfilecon(&work_dir_path, lgetfilecon(&path)?.as_str())?;
+        lsetfilecon(
+            backend,
+            &work_dir_path,
+            lgetfilecon(backend, &path)?.as_str(),
+        )?;
         for entry in path.read_dir()?.flatten() {
-            mount_mirror(&path, &work_dir_path, &entry)?;
+            mount_mirror(&path, &work_dir_path, &entry, backend)?;
         }
     } else if file_type.is_symlink() {
         log::debug!(
@@ -101,15 +151,161 @@ where
             path.display(),
             work_dir_path.display()
         );
-        clone_symlink(&path, &work_dir_path)?;
+        clone_symlink(&path, &work_dir_path, backend)?;
     }
 
     Ok(())
 }
 
+/// Sorts modules into the order they are merged into the tree; the first module
+/// to provide a path wins it. Modules named in `priority` come first, in list
+/// order, then modules by descending `priority=` from their `module.prop`, and
+/// finally the rest alphabetically by id.
+fn sort_modules(modules: &mut [(String, PathBuf, i64)], priority: &[String]) {
+    modules.sort_by_cached_key(|(id, _, prop_priority)| {
+        (
+            priority
+                .iter()
+                .position(|it| it == id)
+                .unwrap_or(usize::MAX),
+            Reverse(*prop_priority),
+            id.clone(),
+        )
+    });
+}
+
+/// The node a `symlink`, `mkdir` or `tmpfs` directive creates. Tmpfs are
+/// mounted from `mountsource`, like the work directory.
+fn synthetic(kind: &MountType, mountsource: &str) -> Option<Synthetic> {
+    match kind {
+        MountType::Symlink { link, .. } => Some(Synthetic::Symlink {
+            link: PathBuf::from(link),
+        }),
+        MountType::Mkdir {
+            mode,
+            uid,
+            gid,
+            context,
+            ..
+        } => Some(Synthetic::Directory {
+            mode: mode.unwrap_or(0o755),
+            uid: uid.unwrap_or(0),
+            gid: gid.unwrap_or(0),
+            context: context.clone(),
+        }),
+        MountType::Tmpfs { size, .. } => Some(Synthetic::Tmpfs {
+            source: mountsource.to_string(),
+            size: size.clone(),
+        }),
+        _ => None,
+    }
+}
+
+fn is_partition(name: &str, partitions: &[String]) -> bool {
+    BUILTIN_PARTITIONS.iter().any(|(p, _)| *p == name) || partitions.iter().any(|p| p == name)
+}
+
+/// Whether `target` names an entry directly in `/system` or in a partition
+/// root. Those never become a tmpfs, so the custom list cannot hide or create
+/// anything there.
+pub fn in_partition_root(target: &Path, partitions: &[String]) -> bool {
+    let mut components = target.strip_prefix("/").unwrap_or(target).iter();
+    if components.next().is_none() {
+        return false;
+    }
+    let path = components.as_path();
+    match path.iter().next() {
+        Some(partition)
+            if target.starts_with("/system")
+                && is_partition(&partition.to_string_lossy(), partitions) =>
+        {
+            path.strip_prefix(partition).is_ok_and(is_top_level)
+        }
+        _ => is_top_level(path),
+    }
+}
+
+/// Adds the `hide`, `replace`, `symlink`, `mkdir` and `tmpfs` directives of
+/// the custom list to the system tree. Targets on other partitions live under
+/// their partition node, as they do in modules; partitions that are neither
+/// builtin nor configured are skipped.
+fn apply_custom_nodes(system: &mut Node, commands: &[Command], config: &Config) -> bool {
+    let mut applied = false;
+    for command in commands {
+        let (MountType::Hide { target }
+        | MountType::Replace { target }
+        | MountType::Symlink { target, .. }
+        | MountType::Mkdir { target, .. }
+        | MountType::Tmpfs { target, .. }) = &command.kind
+        else {
+            continue;
+        };
+
+        let target = Path::new(target);
+        if !matches!(command.kind, MountType::Replace { .. })
+            && in_partition_root(target, &config.partitions)
+        {
+            log::error!(
+                "{} is directly in a partition root, which is never a tmpfs, skip: {}",
+                target.display(),
+                command.kind
+            );
+            continue;
+        }
+
+        let (node, on_device, path) = if let Ok(path) = target.strip_prefix("/system") {
+            (&mut *system, PathBuf::from("/system"), path)
+        } else {
+            let mut components = target.strip_prefix("/").unwrap_or(target).iter();
+            let Some(partition) = components.next().map(|it| it.to_string_lossy().to_string())
+            else {
+                continue;
+            };
+            if !is_partition(&partition, &config.partitions) {
+                log::error!(
+                    "{partition} is not a known partition, skip: {}",
+                    command.kind
+                );
+                continue;
+            }
+            let real = Path::new("/").join(&partition);
+            if !real.is_dir() {
+                log::warn!("{} does not exist, skip: {}", real.display(), command.kind);
+                continue;
+            }
+
+            let node = system
+                .children
+                .entry(partition.clone())
+                .or_insert_with(|| Node::new_root(partition));
+            (node, real, components.as_path())
+        };
+
+        let done = match &command.kind {
+            MountType::Hide { .. } => node.hide(&on_device, path),
+            MountType::Replace { .. } => node.replace(&on_device, path),
+            kind => synthetic(kind, &config.mountsource)
+                .is_some_and(|it| node.create(&on_device, path, it)),
+        };
+        if done {
+            log::debug!("custom list: {}", command.kind);
+            applied = true;
+        } else {
+            log::warn!(
+                "{} does not exist, skip: {}",
+                target.display(),
+                command.kind
+            );
+        }
+    }
+
+    applied
+}
+
 pub fn collect_module_files(
     module_dir: &Path,
-    extra_partitions: &[String],
+    rules_dir: &Path,
+    config: &Config,
 ) -> Result<Option<Node>> {
     let mut root = Node::new_root("");
     let mut system = Node::new_root("system");
@@ -118,13 +314,13 @@ pub fn collect_module_files(
 
     log::debug!("begin collect module files: {}", module_root.display());
 
+    let mut modules = Vec::new();
     for entry in module_root.read_dir()?.flatten() {
         if !entry.file_type()?.is_dir() {
             continue;
         }
 
         let id = entry.file_name().to_str().unwrap().to_string();
-        log::debug!("processing new module: {id}");
 
         let prop = entry.path().join("module.prop");
         if !prop.exists() {
@@ -132,41 +328,61 @@ pub fn collect_module_files(
             continue;
         }
         let string = fs::read_to_string(prop)?;
+        let mut prop_priority = 0;
         for line in string.lines() {
             if line.starts_with("id")
                 && let Some((_, value)) = line.split_once('=')
             {
                 validate_module_id(value)?;
+            } else if let Some((key, value)) = line.split_once('=')
+                && key.trim() == "priority"
+            {
+                prop_priority = value.trim().parse().unwrap_or_else(|_| {
+                    log::warn!("invalid priority {value:?} in module {id}, using 0");
+                    0
+                });
             }
         }
 
-        if entry.path().join(defs::DISABLE_FILE_NAME).exists()
-            || entry.path().join(defs::REMOVE_FILE_NAME).exists()
-            || entry.path().join(defs::SKIP_MOUNT_FILE_NAME).exists()
+        modules.push((id, entry.path(), prop_priority));
+    }
+    sort_modules(&mut modules, &config.priority);
+
+    for (id, path, _) in modules {
+        log::debug!("processing new module: {id}");
+
+        if path.join(defs::DISABLE_FILE_NAME).exists()
+            || path.join(defs::REMOVE_FILE_NAME).exists()
+            || path.join(defs::SKIP_MOUNT_FILE_NAME).exists()
         {
             log::debug!("skipped module {id}, due to disable/remove/skip_mount");
             continue;
         }
 
-        let mod_system = entry.path().join("system");
+        let rules = ModuleRules::load(rules_dir, &id);
+        if rules.ignores("system") {
+            log::debug!("skipped module {id}, due to mount mode ignore");
+            continue;
+        }
+
+        let mod_system = path.join("system");
         if !mod_system.is_dir() {
             log::debug!("{id} due not modify system");
             continue;
         }
 
-        log::debug!("collecting {}", entry.path().display());
+        log::debug!("collecting {}", path.display());
 
-        has_file |= system.collect_module_files(&mod_system)?;
+        has_file |= system.collect_module_files(&mod_system, &id, &rules)?;
     }
 
-    if has_file {
-        const BUILTIN_PARTITIONS: [(&str, bool); 4] = [
-            ("vendor", true),
-            ("system_ext", true),
-            ("product", true),
-            ("odm", false),
-        ];
+    has_file |= apply_custom_nodes(
+        &mut system,
+        COMMAND_LIST.get().map_or(&[], Vec::as_slice),
+        config,
+    );
 
+    if has_file {
         for (partition, require_symlink) in BUILTIN_PARTITIONS {
             let path_of_root = Path::new("/").join(partition);
             let path_of_system = Path::new("/system").join(partition);
@@ -178,7 +394,7 @@ pub fn collect_module_files(
             }
         }
 
-        for partition in extra_partitions {
+        for partition in &config.partitions {
             if BUILTIN_PARTITIONS.iter().any(|(p, _)| p == partition) {
                 continue;
             }
@@ -206,13 +422,17 @@ pub fn collect_module_files(
     }
 }
 
-pub fn clone_symlink<S>(src: S, dst: S) -> Result<()>
+pub fn clone_symlink<S>(src: S, dst: S, backend: &dyn MountBackend) -> Result<()>
 where
     S: AsRef<Path>,
 {
     let src_symlink = read_link(src.as_ref())?;
     symlink(&src_symlink, dst.as_ref())?;
-    lsetfilecon(dst.as_ref(), lgetfilecon(src.as_ref())?.as_str())?;
+    lsetfilecon(
+        backend,
+        dst.as_ref(),
+        lgetfilecon(backend, src.as_ref())?.as_str(),
+    )?;
     log::debug!(
         "clone symlink {} -> {}({})",
         dst.as_ref().display(),
@@ -221,3 +441,15 @@ where
     );
     Ok(())
 }
+
+/// Creates a symlink to `link` at `dst`, labelled like the directory it is in.
+pub fn new_symlink(link: &Path, dst: &Path, backend: &dyn MountBackend) -> Result<()> {
+    symlink(link, dst)?;
+    lsetfilecon(
+        backend,
+        dst,
+        lgetfilecon(backend, dst.parent().unwrap_or(dst))?.as_str(),
+    )?;
+    log::debug!("new symlink {} -> {}", dst.display(), link.display());
+    Ok(())
+}
@@ -4,106 +4,195 @@
 #![deny(clippy::all, clippy::pedantic)]
 #![warn(clippy::nursery)]
 
+mod api;
 mod bind_mount;
+mod cli;
+mod condition;
 mod config;
 mod defs;
 mod errors;
+mod ignore;
 mod magic_mount;
 mod misc;
+mod mount_backend;
 mod mount_list;
 mod parser;
+mod paths;
+mod report;
 mod scanner;
 mod utils;
+mod validate;
 
-use rustix::mount::{MountFlags, mount};
+use clap::Parser;
 
 use crate::{
     bind_mount::bind_mount,
+    cli::{Cli, Commands, Output},
     config::{Config, handle_gen_config, handle_save_config, handle_show_config},
-    defs::MODULE_PATH,
     errors::Result,
     misc::{cleanup, emulated_soft_reboot},
+    mount_backend::{MountBackend, RustixBackend},
+    paths::Paths,
+    report::Report,
     utils::ksucalls::unmount,
 };
 
+fn plan(paths: &Paths, output: Output) -> Result<()> {
+    misc::init_list(paths);
+    let config = Config::load_or_default(&paths.config_file);
+    let plan = magic_mount::plan::Plan::new(paths, &config)?;
+    match output {
+        Output::Text => print!("{plan}"),
+        Output::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+        Output::Quiet => {}
+    }
+    Ok(())
+}
+
+fn run_command(command: &Commands, paths: &Paths, output: Output) -> Result<()> {
+    match command {
+        Commands::ShowConfig => {
+            handle_show_config(paths, output)?;
+        }
+        Commands::EmulatedSoftReboot => {
+            emulated_soft_reboot(paths)?;
+        }
+        Commands::SaveConfig { payload } => {
+            handle_save_config(paths, payload, output)?;
+        }
+        Commands::GenConfig => {
+            handle_gen_config(paths, output)?;
+        }
+        Commands::Modules => {
+            let config = Config::load_or_default(&paths.config_file);
+            let modules =
+                scanner::list_modules(&paths.module_dir, &paths.rules_dir(), &config.partitions);
+            output.json(&scanner::show_modules(modules, paths.mount_status())?)?;
+        }
+        Commands::ModuleRules { action } => {
+            scanner::handle_module_rules(action, paths.rules_dir(), output)?;
+        }
+        Commands::Conflicts => {
+            misc::init_list(paths);
+            let config = Config::load_or_default(&paths.config_file);
+            output.list(&magic_mount::conflicts(paths, &config)?)?;
+        }
+        Commands::Plan => {
+            plan(paths, output)?;
+        }
+        Commands::Report => {
+            report::handle_report(paths.last_run_report(), output)?;
+        }
+        Commands::Validate => {
+            validate::handle_validate(paths, output)?;
+        }
+        Commands::Api { request } => {
+            api::handle_api(request.as_deref(), paths, output)?;
+        }
+        Commands::Version => {
+            if output != Output::Quiet {
+                println!("{{ \"version\": \"{}\" }}", env!("CARGO_PKG_VERSION"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Logs a failed phase with its full context chain and records it in the report.
+fn phase_failed(report: &mut Report, phase: &str, e: errors::Error) -> errors::Error {
+    let e = anyhow::Error::from(e);
+    for cause in e.chain() {
+        log::error!("{cause:#?}");
+    }
+    log::error!("{:#?}", e.backtrace());
+    report.error(phase, &e);
+    errors::Error::AnyHow(e)
+}
+
+/// Ends a boot that cannot go on, saving the report with the failed phase.
+fn boot_failed(mut report: Report, paths: &Paths, phase: &str, e: errors::Error) -> errors::Error {
+    let e = phase_failed(&mut report, phase, e);
+    save_report(report, paths);
+    e
+}
+
 fn main() -> Result<()> {
     #[cfg(not(any(target_os = "linux", target_os = "android")))]
     compile_error!("unsupported platform!");
 
-    misc::pre_init();
+    let cli = Cli::parse();
+    let output = cli.output();
+    let paths = Paths::from_args(cli.paths);
+    misc::init(cli.quiet);
 
-    let args: Vec<_> = std::env::args().collect();
-    let config = Config::load(defs::CONFIG_FILE)?;
-    let modules = scanner::list_modules(MODULE_PATH, &config.partitions);
+    match &cli.command {
+        Some(command) => run_command(command, &paths, output),
+        None if cli.dry_run => plan(&paths, output),
+        None => boot_mount(&paths),
+    }
+}
 
-    if let Some(arg) = args.get(1) {
-        match arg.as_str() {
-            "show-config" => {
-                handle_show_config()?;
-            }
-            "emulated-soft-reboot" => {
-                emulated_soft_reboot()?;
-            }
-            "save-config" => {
-                handle_save_config(&args[2..])?;
-            }
-            "gen-config" => {
-                handle_gen_config()?;
-            }
-            "modules" => {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&scanner::show_modules(modules)?)?
-                );
-            }
-            "version" => {
-                println!("{{ \"version\": \"{}\" }}", env!("CARGO_PKG_VERSION"));
-            }
-            _ => {}
-        }
+/// Mounts every enabled module and the custom binds, then records the result.
+fn boot_mount(paths: &Paths) -> Result<()> {
+    misc::pre_init(paths);
 
-        return Ok(());
+    let (config, config_errors) = Config::load_for_boot(&paths.config_file);
+    let mut report = Report::new(&config);
+    for e in &config_errors {
+        report.error("config", e);
     }
+    let modules = report.time("scan", || {
+        scanner::list_modules(&paths.module_dir, &paths.rules_dir(), &config.partitions)
+    });
 
-    let _ = std::fs::write(defs::SCANNED_LIST, &serde_json::to_string_pretty(&modules)?);
+    let scanned = match serde_json::to_string_pretty(&modules) {
+        Ok(scanned) => scanned,
+        Err(e) => return Err(boot_failed(report, paths, "scan", e.into())),
+    };
+    let _ = std::fs::write(paths.scanned_list(), &scanned);
 
     log::info!("Magic Mount Starting");
     log::info!("config info:\n{config}");
 
-    log::debug!(
-        "current selinux: {}",
-        std::fs::read_to_string("/proc/self/attr/current")?
-    );
+    match std::fs::read_to_string("/proc/self/attr/current") {
+        Ok(context) => log::debug!("current selinux: {context}"),
+        Err(e) => log::debug!("current selinux: unknown ({e})"),
+    }
 
-    let mounts = mount_list::MountList::persistent()?;
+    let mounts = match mount_list::MountList::persistent(paths) {
+        Ok(mounts) => mounts,
+        Err(e) => return Err(boot_failed(report, paths, "mount_list", e)),
+    };
+    let backend = RustixBackend;
 
-    if let Err(e) = mount(
-        &config.mountsource,
-        "/debug_ramdisk",
-        "tmpfs",
-        MountFlags::empty(),
-        None,
-    ) {
+    if let Err(e) = backend.tmpfs(&config.mountsource, &paths.work_root, None) {
         log::error!("mount tmpfs failed: {e}");
-        std::process::exit(1);
+        return Err(boot_failed(report, paths, "tmpfs", e));
     }
 
-    let magic_mount_result = magic_mount::magic_mount(
-        MODULE_PATH,
-        &config.mountsource,
-        &config.partitions,
-        config.umount,
-        &mounts,
-    );
+    let magic_mount_result = report
+        .time("collect", || magic_mount::collect(paths, &config))
+        .and_then(|root| {
+            report.time("magic_mount", || {
+                magic_mount::magic_mount(root.as_ref(), &config, paths, &mounts, &backend)
+            })
+        });
+    if let Err(e) = magic_mount::status::save(paths.mount_status()) {
+        log::warn!("save mount status failed: {e:#}");
+    }
     let bind_mount_result = if magic_mount_result.is_ok() {
-        Some(bind_mount(config.umount, &mounts))
+        Some(report.time("bind_mount", || {
+            bind_mount(config.umount, &paths.work_root, &mounts, &backend)
+        }))
     } else {
         None
     };
 
-    cleanup();
-    unmount()?;
+    cleanup(&paths.work_root, &backend);
+    let umount_result = report.time("ksu_umount", unmount);
 
+    let mut result = Ok(());
     match magic_mount_result {
         Ok(()) => {
             log::info!("Magic Mount Completed Successfully");
@@ -111,31 +200,35 @@ fn main() -> Result<()> {
         Err(e) => {
             log::error!("Magic Mount Failed");
             log::error!("Dont run bind mount stage!!");
-            let e = anyhow::Error::from(e);
-            for cause in e.chain() {
-                log::error!("{cause:#?}");
-            }
-            log::error!("{:#?}", e.backtrace());
-            return Err(errors::Error::AnyHow(e));
+            result = Err(phase_failed(&mut report, "magic_mount", e));
         }
     }
 
-    if let Some(bind_mount_result) = bind_mount_result {
-        match bind_mount_result {
-            Ok(()) => {
-                log::info!("Bind mount Completed Successfully");
-            }
-            Err(e) => {
-                log::error!("Bind mount Failed");
-                let e = anyhow::Error::from(e);
-                for cause in e.chain() {
-                    log::error!("{cause:#?}");
-                }
-                log::error!("{:#?}", e.backtrace());
-                return Err(errors::Error::AnyHow(e));
-            }
+    match bind_mount_result {
+        Some(Ok(())) => {
+            log::info!("Bind mount Completed Successfully");
+        }
+        Some(Err(e)) => {
+            log::error!("Bind mount Failed");
+            result = Err(phase_failed(&mut report, "bind_mount", e));
         }
+        None => {}
     }
 
-    Ok(())
+    if let Err(e) = umount_result {
+        log::error!("KernelSU umount Failed");
+        let e = phase_failed(&mut report, "ksu_umount", e);
+        result = result.and(Err(e));
+    }
+
+    save_report(report, paths);
+
+    result
+}
+
+fn save_report(mut report: Report, paths: &Paths) {
+    report.finish(magic_mount::stats());
+    if let Err(e) = report.save(paths.last_run_report()) {
+        log::warn!("save boot report failed: {e:#}");
+    }
 }
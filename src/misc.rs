@@ -1,11 +1,15 @@
 // Copyright (C) 2026 meta-magic_mount-rs developers
 // SPDX-License-Identifier: GPL-v3
 
-use rustix::mount::{UnmountFlags, unmount};
+use std::path::Path;
 
-use crate::{defs, errors::Result, mount_list, utils::ksucalls};
+use rustix::mount::UnmountFlags;
 
-fn init_logger() {
+use crate::{
+    errors::Result, mount_backend::MountBackend, mount_list, paths::Paths, utils::ksucalls,
+};
+
+fn init_logger(level: log::LevelFilter) {
     #[cfg(not(target_os = "android"))]
     {
         use std::io::Write;
@@ -21,22 +25,21 @@ fn init_logger() {
                 record.args()
             )
         });
-        builder.filter_level(log::LevelFilter::Debug).init();
+        builder.filter_level(level).init();
     }
 
     #[cfg(target_os = "android")]
     {
         android_logger::init_once(
             android_logger::Config::default()
-                .with_max_level(log::LevelFilter::Debug)
+                .with_max_level(level)
                 .with_tag("MagicMount"),
         );
     }
 }
 
-fn init_list() {
-    super::parser::COMMAND_LIST
-        .get_or_init(|| super::parser::parser_custom(defs::CUSTOM_LIST_PATH));
+pub fn init_list(paths: &Paths) {
+    super::parser::COMMAND_LIST.get_or_init(|| super::parser::load_commands(paths));
 }
 
 fn init_hook() {
@@ -47,21 +50,30 @@ fn init_hook() {
     }));
 }
 
-pub fn emulated_soft_reboot() -> Result<()> {
-    mount_list::MountList::unmount_persisted()
+pub fn emulated_soft_reboot(paths: &Paths) -> Result<()> {
+    mount_list::MountList::unmount_persisted(paths)
 }
 
-pub fn cleanup() {
-    if let Err(e) = unmount("/debug_ramdisk", UnmountFlags::DETACH) {
+pub fn cleanup(work_root: &Path, backend: &dyn MountBackend) {
+    if let Err(e) = backend.unmount(work_root, UnmountFlags::DETACH) {
         log::warn!("failed to unmount tempdir: {e}");
     }
 }
 
-pub fn pre_init() {
-    init_logger();
+/// Sets up logging and the panic hook; every command needs these.
+pub fn init(quiet: bool) {
+    init_logger(if quiet {
+        log::LevelFilter::Error
+    } else {
+        log::LevelFilter::Debug
+    });
     init_hook();
+}
+
+/// What the boot mount needs before it starts.
+pub fn pre_init(paths: &Paths) {
     ksucalls::check_ksu();
-    init_list();
+    init_list(paths);
 
     if std::env::var("KSU_LATE_LOAD").is_ok() {
         log::info!("late load mode!!");
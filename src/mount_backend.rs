@@ -0,0 +1,239 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{ffi::CString, path::Path};
+
+use anyhow::Context;
+use extattr::{Flags as XattrFlags, lgetxattr, lsetxattr};
+use rustix::mount::{
+    MountFlags, MountPropagationFlags, UnmountFlags, mount, mount_bind, mount_bind_recursive,
+    mount_change, mount_move, mount_remount, unmount,
+};
+
+use crate::errors::Result;
+
+/// Every mount table and xattr operation the mount engine performs.
+pub trait MountBackend {
+    fn bind(&self, source: &Path, target: &Path) -> Result<()>;
+    fn bind_recursive(&self, source: &Path, target: &Path) -> Result<()>;
+    fn move_mount(&self, source: &Path, target: &Path) -> Result<()>;
+    fn remount(&self, target: &Path, flags: MountFlags) -> Result<()>;
+    fn change(&self, target: &Path, flags: MountPropagationFlags) -> Result<()>;
+    /// Mounts a tmpfs, limited to `size` (as in `size=`) when given.
+    fn tmpfs(&self, source: &str, target: &Path, size: Option<&str>) -> Result<()>;
+    fn unmount(&self, target: &Path, flags: UnmountFlags) -> Result<()>;
+    fn setxattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()>;
+    fn getxattr(&self, path: &Path, name: &str) -> Result<Vec<u8>>;
+}
+
+/// The real backend, backed by `rustix` syscalls.
+pub struct RustixBackend;
+
+impl MountBackend for RustixBackend {
+    fn bind(&self, source: &Path, target: &Path) -> Result<()> {
+        mount_bind(source, target)?;
+        Ok(())
+    }
+
+    fn bind_recursive(&self, source: &Path, target: &Path) -> Result<()> {
+        mount_bind_recursive(source, target)?;
+        Ok(())
+    }
+
+    fn move_mount(&self, source: &Path, target: &Path) -> Result<()> {
+        mount_move(source, target)?;
+        Ok(())
+    }
+
+    fn remount(&self, target: &Path, flags: MountFlags) -> Result<()> {
+        mount_remount(target, flags, "")?;
+        Ok(())
+    }
+
+    fn change(&self, target: &Path, flags: MountPropagationFlags) -> Result<()> {
+        mount_change(target, flags)?;
+        Ok(())
+    }
+
+    fn tmpfs(&self, source: &str, target: &Path, size: Option<&str>) -> Result<()> {
+        let data = size
+            .map(|size| CString::new(format!("size={size}")))
+            .transpose()
+            .context("invalid tmpfs size")?;
+        mount(
+            source,
+            target,
+            "tmpfs",
+            MountFlags::empty(),
+            data.as_deref(),
+        )?;
+        Ok(())
+    }
+
+    fn unmount(&self, target: &Path, flags: UnmountFlags) -> Result<()> {
+        unmount(target, flags)?;
+        Ok(())
+    }
+
+    fn setxattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+        lsetxattr(path, name, value, XattrFlags::empty())?;
+        Ok(())
+    }
+
+    fn getxattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+        Ok(lgetxattr(path, name)?)
+    }
+}
+
+#[cfg(test)]
+pub use recording::{MountOp, RecordingBackend};
+
+#[cfg(test)]
+mod recording {
+    use std::{cell::RefCell, path::PathBuf};
+
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum MountOp {
+        Bind {
+            source: PathBuf,
+            target: PathBuf,
+        },
+        BindRecursive {
+            source: PathBuf,
+            target: PathBuf,
+        },
+        Move {
+            source: PathBuf,
+            target: PathBuf,
+        },
+        Remount {
+            target: PathBuf,
+            flags: MountFlags,
+        },
+        Change {
+            target: PathBuf,
+            flags: MountPropagationFlags,
+        },
+        Tmpfs {
+            source: String,
+            target: PathBuf,
+            size: Option<String>,
+        },
+        Unmount {
+            target: PathBuf,
+        },
+        SetXattr {
+            path: PathBuf,
+            name: String,
+            value: Vec<u8>,
+        },
+    }
+
+    /// An in-memory backend that records operations instead of performing them.
+    ///
+    /// Reading an xattr that was never set returns `default_xattr`.
+    pub struct RecordingBackend {
+        pub ops: RefCell<Vec<MountOp>>,
+        xattrs: RefCell<FxHashMap<(PathBuf, String), Vec<u8>>>,
+        default_xattr: Vec<u8>,
+    }
+
+    impl Default for RecordingBackend {
+        fn default() -> Self {
+            Self {
+                ops: RefCell::new(Vec::new()),
+                xattrs: RefCell::new(FxHashMap::default()),
+                default_xattr: b"u:object_r:system_file:s0".to_vec(),
+            }
+        }
+    }
+
+    impl RecordingBackend {
+        fn push(&self, op: MountOp) {
+            self.ops.borrow_mut().push(op);
+        }
+    }
+
+    impl MountBackend for RecordingBackend {
+        fn bind(&self, source: &Path, target: &Path) -> Result<()> {
+            self.push(MountOp::Bind {
+                source: source.to_path_buf(),
+                target: target.to_path_buf(),
+            });
+            Ok(())
+        }
+
+        fn bind_recursive(&self, source: &Path, target: &Path) -> Result<()> {
+            self.push(MountOp::BindRecursive {
+                source: source.to_path_buf(),
+                target: target.to_path_buf(),
+            });
+            Ok(())
+        }
+
+        fn move_mount(&self, source: &Path, target: &Path) -> Result<()> {
+            self.push(MountOp::Move {
+                source: source.to_path_buf(),
+                target: target.to_path_buf(),
+            });
+            Ok(())
+        }
+
+        fn remount(&self, target: &Path, flags: MountFlags) -> Result<()> {
+            self.push(MountOp::Remount {
+                target: target.to_path_buf(),
+                flags,
+            });
+            Ok(())
+        }
+
+        fn change(&self, target: &Path, flags: MountPropagationFlags) -> Result<()> {
+            self.push(MountOp::Change {
+                target: target.to_path_buf(),
+                flags,
+            });
+            Ok(())
+        }
+
+        fn tmpfs(&self, source: &str, target: &Path, size: Option<&str>) -> Result<()> {
+            self.push(MountOp::Tmpfs {
+                source: source.to_string(),
+                target: target.to_path_buf(),
+                size: size.map(str::to_string),
+            });
+            Ok(())
+        }
+
+        fn unmount(&self, target: &Path, _flags: UnmountFlags) -> Result<()> {
+            self.push(MountOp::Unmount {
+                target: target.to_path_buf(),
+            });
+            Ok(())
+        }
+
+        fn setxattr(&self, path: &Path, name: &str, value: &[u8]) -> Result<()> {
+            self.xattrs
+                .borrow_mut()
+                .insert((path.to_path_buf(), name.to_string()), value.to_vec());
+            self.push(MountOp::SetXattr {
+                path: path.to_path_buf(),
+                name: name.to_string(),
+                value: value.to_vec(),
+            });
+            Ok(())
+        }
+
+        fn getxattr(&self, path: &Path, name: &str) -> Result<Vec<u8>> {
+            Ok(self
+                .xattrs
+                .borrow()
+                .get(&(path.to_path_buf(), name.to_string()))
+                .cloned()
+                .unwrap_or_else(|| self.default_xattr.clone()))
+        }
+    }
+}
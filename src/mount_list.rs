@@ -12,8 +12,8 @@ use std::{
 use rustix::mount::{UnmountFlags, unmount};
 
 use crate::{
-    defs,
     errors::{Error, Result},
+    paths::Paths,
 };
 
 pub struct MountList {
@@ -40,8 +40,8 @@ impl MountList {
         })
     }
 
-    pub fn persistent() -> Result<Self> {
-        Self::new(defs::UMOUNT_LIST)
+    pub fn persistent(paths: &Paths) -> Result<Self> {
+        Self::new(paths.umount_list())
     }
 
     pub fn record<T>(&self, target: T)
@@ -99,8 +99,8 @@ impl MountList {
         Ok(())
     }
 
-    pub fn unmount_persisted() -> Result<()> {
-        unmount_from(Path::new(defs::UMOUNT_LIST), |target| {
+    pub fn unmount_persisted(paths: &Paths) -> Result<()> {
+        unmount_from(&paths.umount_list(), |target| {
             unmount(target, UnmountFlags::DETACH).map_err(Error::from)
         })
     }
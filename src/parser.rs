@@ -1,179 +1,961 @@
 // Copyright (C) 2026 meta-magic_mount-rs developers
 // SPDX-License-Identifier: GPL-v3
 
-use std::{fmt, fs, path::Path, sync::OnceLock};
+use std::{
+    fmt, fs, mem,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
-use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
 
-pub static COMMAND_LIST: OnceLock<Vec<MountType>> = OnceLock::new();
-static FILES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+use crate::{
+    condition::{Condition, Facts},
+    defs,
+    errors::ParseError,
+    ignore::{glob_paths, is_glob},
+    paths::Paths,
+    scanner,
+    utils::validate_module_id,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+pub static COMMAND_LIST: OnceLock<Vec<Command>> = OnceLock::new();
+
+/// The propagation `--propagation=` sets on a bind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Propagation {
+    Private,
+    Slave,
+    Shared,
+}
+
+/// Options of a `bind` line. The default is a read-only, non-recursive bind.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BindOptions {
+    pub rw: bool,
+    /// Binds the mounts below the source too.
+    pub recursive: bool,
+    pub nosuid: bool,
+    pub nodev: bool,
+    pub noexec: bool,
+    pub propagation: Option<Propagation>,
+    /// The label the source gets before it is mounted.
+    pub context: Option<String>,
+    /// A missing source or a failed mount is not an error.
+    pub optional: bool,
+}
+
+impl BindOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Removes the options between the verb and the paths of a `bind` line.
+    /// A `--` ends them, so a source may start with `--` too.
+    fn take(tokens: &mut Vec<(usize, String)>) -> Result<Self, (usize, ParseError)> {
+        let mut options = Self::default();
+        let count = tokens
+            .iter()
+            .skip(1)
+            .take_while(|(_, it)| it.starts_with("--"))
+            .count();
+        // options stop at a `--`, which is dropped as well
+        let (count, marker) = tokens[1..=count]
+            .iter()
+            .position(|(_, it)| it == "--")
+            .map_or((count, 0), |it| (it, 1));
+        let flags: Vec<_> = tokens.drain(1..=count + marker).take(count).collect();
+        for (column, option) in flags {
+            let invalid = |argument, value: &str| {
+                (
+                    column,
+                    ParseError::InvalidArgument {
+                        command: "bind".to_string(),
+                        argument,
+                        value: value.to_string(),
+                    },
+                )
+            };
+            match option.as_str() {
+                "--rw" => options.rw = true,
+                "--rbind" => options.recursive = true,
+                "--nosuid" => options.nosuid = true,
+                "--nodev" => options.nodev = true,
+                "--noexec" => options.noexec = true,
+                "--optional" => options.optional = true,
+                _ if option.starts_with("--context=") => {
+                    let context = &option["--context=".len()..];
+                    if context.is_empty() {
+                        return Err(invalid("context", context));
+                    }
+                    options.context = Some(context.to_string());
+                }
+                _ => {
+                    let Some(propagation) = option.strip_prefix("--propagation=") else {
+                        return Err(invalid("option", &option));
+                    };
+                    options.propagation = Some(match propagation {
+                        "private" => Propagation::Private,
+                        "slave" => Propagation::Slave,
+                        "shared" => Propagation::Shared,
+                        _ => return Err(invalid("propagation", propagation)),
+                    });
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+impl fmt::Display for BindOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options = [
+            (self.rw, "--rw"),
+            (self.recursive, "--rbind"),
+            (self.nosuid, "--nosuid"),
+            (self.nodev, "--nodev"),
+            (self.noexec, "--noexec"),
+            (self.optional, "--optional"),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, option)| option.to_string())
+        .collect::<Vec<_>>();
+        if let Some(propagation) = self.propagation {
+            let propagation = match propagation {
+                Propagation::Private => "private",
+                Propagation::Slave => "slave",
+                Propagation::Shared => "shared",
+            };
+            options.push(format!("--propagation={propagation}"));
+        }
+        if let Some(context) = &self.context {
+            options.push(format!("--context={context}"));
+        }
+        f.write_str(&options.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum MountType {
-    Mount { source: String, target: String },
-    Ignore { source: String },
+    Mount {
+        source: String,
+        target: String,
+        #[serde(skip_serializing_if = "BindOptions::is_default")]
+        options: BindOptions,
+    },
+    /// A literal path or a glob.
+    Ignore {
+        source: String,
+    },
+    IgnoreRe {
+        pattern: String,
+    },
+    /// A system path, or a glob of them, no module may provide.
+    IgnoreTarget {
+        target: String,
+    },
+    /// A system path to remove, as if a module shipped a whiteout for it.
+    Hide {
+        target: String,
+    },
+    /// A system directory to empty, as if a module shipped it with `.replace`.
+    Replace {
+        target: String,
+    },
+    /// A symlink at `target` that points at `link`.
+    Symlink {
+        link: String,
+        target: String,
+    },
+    /// A new directory. Unset attributes default to `0755 0 0` and the
+    /// context of the directory it is created in.
+    Mkdir {
+        target: String,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        context: Option<String>,
+    },
+    /// An empty, writable tmpfs; `size` is passed to the mount as is.
+    Tmpfs {
+        target: String,
+        size: Option<String>,
+    },
+}
+
+impl MountType {
+    /// Resolves relative sources against `dir`; targets are always absolute.
+    fn relative_to(self, dir: &Path) -> Self {
+        let resolve = |source: String| {
+            if Path::new(&source).is_absolute() {
+                source
+            } else {
+                dir.join(source).to_string_lossy().into_owned()
+            }
+        };
+
+        match self {
+            Self::Mount {
+                source,
+                target,
+                options,
+            } => Self::Mount {
+                source: resolve(source),
+                target,
+                options,
+            },
+            Self::Ignore { source } => Self::Ignore {
+                source: resolve(source),
+            },
+            other => other,
+        }
+    }
 }
 
 impl fmt::Display for MountType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Mount { source, target } => f.write_str(&format!("{source} -> {target}")),
+            Self::Mount {
+                source,
+                target,
+                options,
+            } if !options.is_default() => f.write_str(&format!("{source} -> {target} ({options})")),
+            Self::Mount { source, target, .. } => f.write_str(&format!("{source} -> {target}")),
             Self::Ignore { source } => f.write_str(&format!("ignored {source}")),
+            Self::IgnoreRe { pattern } => f.write_str(&format!("ignored /{pattern}/")),
+            Self::IgnoreTarget { target } => f.write_str(&format!("ignored target {target}")),
+            Self::Hide { target } => f.write_str(&format!("hidden {target}")),
+            Self::Replace { target } => f.write_str(&format!("replaced {target}")),
+            Self::Symlink { link, target } => f.write_str(&format!("symlink {target} -> {link}")),
+            Self::Mkdir { target, .. } => f.write_str(&format!("created {target}")),
+            Self::Tmpfs { target, .. } => f.write_str(&format!("tmpfs {target}")),
         }
     }
 }
 
-pub fn parser_custom<P>(path: P) -> Vec<MountType>
-where
-    P: AsRef<Path>,
-{
-    fs::read_to_string(path.as_ref()).map_or_else(|_| Vec::new(), |s| parse(&s))
+/// Where a command or error was found. `line` and `column` are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
 }
 
-fn parse(content: &str) -> Vec<MountType> {
-    let mut types = Vec::new();
-    for line in content.lines() {
-        let line = line.trim();
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Command {
+    pub span: Span,
+    pub kind: MountType,
+    /// Whether the command sits in an `if` block, directly or through an include.
+    pub conditional: bool,
+    /// Whether the line used any variable.
+    pub expanded: bool,
+    /// The module whose list the command came from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseIssue {
+    pub span: Span,
+    pub message: String,
+    pub error: ParseError,
+}
 
-        if line.starts_with('#') || line.is_empty() {
-            continue;
+impl ParseIssue {
+    fn new(span: Span, error: ParseError) -> Self {
+        Self {
+            span,
+            message: error.to_string(),
+            error,
         }
+    }
+}
 
-        if line.starts_with("bind") {
-            match parse_bind(line) {
-                Some(s) => {
-                    log::debug!("new bind command: {s}");
-                    types.push(s);
-                }
-                None => {
-                    log::debug!("failed to parse {line}");
-                }
+/// Everything found in a custom list and the files it includes.
+#[derive(Debug, Default, Serialize)]
+pub struct ParseReport {
+    pub commands: Vec<Command>,
+    pub errors: Vec<ParseIssue>,
+    /// Lines that are valid but probably not what was meant.
+    pub warnings: Vec<ParseIssue>,
+    /// Variables defined so far; `set` lines apply to everything after them,
+    /// included files too.
+    #[serde(skip)]
+    variables: Variables,
+    /// Canonical paths of the files being parsed, outermost first.
+    #[serde(skip)]
+    chain: Vec<PathBuf>,
+    /// Canonical paths of every file parsed so far.
+    #[serde(skip)]
+    seen: FxHashSet<PathBuf>,
+    /// The id and directory of the module whose list is being parsed.
+    #[serde(skip)]
+    module: Option<(String, PathBuf)>,
+}
+
+pub enum Statement {
+    Mount(MountType),
+    Include(String),
+    If(Condition),
+    Else,
+    EndIf,
+    Set { name: String, value: String },
+}
+
+/// An `if` block that is still open.
+struct Block {
+    span: Span,
+    /// Whether the enclosing lines are in effect.
+    outer: bool,
+    /// Whether the current branch is in effect on its own.
+    taken: bool,
+    seen_else: bool,
+}
+
+impl ParseReport {
+    /// Parses `path` and its drop-ins, following `file`/`add` includes. A
+    /// missing file is an empty list.
+    pub fn from_file<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_file_with(path, Facts::detect(), Variables::default())
+    }
+
+    /// Like [`Self::from_file`], evaluating `if` blocks against `facts` and
+    /// starting from `variables`. The drop-in lists in `<path>.d/*.list`
+    /// follow `path` in lexical order.
+    pub fn from_file_with<P>(path: P, facts: &Facts, variables: Variables) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut drop_ins = path.as_os_str().to_owned();
+        drop_ins.push(".d");
+        let drop_ins = glob_paths(&Path::new(&drop_ins).join("*.list"));
+
+        let mut report = Self {
+            variables,
+            ..Self::default()
+        };
+        let files = path.exists().then(|| path.to_path_buf());
+        for file in files.into_iter().chain(drop_ins) {
+            if let Err(e) = report.include(&file, facts, false) {
+                log::warn!("failed to parse {}: {e}", file.display());
+            }
+        }
+        report
+    }
+
+    /// Parses an included file unless it is already being parsed further up
+    /// the chain of includes. A file reached again through another chain, e.g.
+    /// in a diamond of includes, is only parsed the first time.
+    fn include(&mut self, path: &Path, facts: &Facts, conditional: bool) -> Result<(), ParseError> {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(start) = self.chain.iter().position(|it| *it == canonical) {
+            let chain: Vec<_> = self.chain[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|it| it.display().to_string())
+                .collect();
+            return Err(ParseError::IncludeCycle {
+                chain: chain.join(" -> "),
+            });
+        }
+
+        if self.seen.contains(&canonical) {
+            log::debug!("{} is already included", path.display());
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| ParseError::Include {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        self.seen.insert(canonical.clone());
+        self.chain.push(canonical);
+        self.parse(path, &content, facts, conditional);
+        self.chain.pop();
+        Ok(())
+    }
+
+    /// Reads every file an `include` pattern matches, warning when there are none.
+    fn include_all(&mut self, span: &Span, include: &str, facts: &Facts, conditional: bool) {
+        let paths = include_paths(&span.file, include);
+        if paths.is_empty() {
+            self.warnings.push(ParseIssue::new(
+                span.clone(),
+                ParseError::EmptyInclude {
+                    pattern: include.to_string(),
+                },
+            ));
+        }
+        for path in paths {
+            log::debug!("new file: {}", path.display());
+            if let Err(error) = self.include(&path, facts, conditional) {
+                self.errors.push(ParseIssue::new(span.clone(), error));
+            }
+        }
+    }
+
+    /// Parses one file. Blocks cannot span files, so each file tracks its own;
+    /// `conditional` is set when the file was included from inside a block.
+    fn parse(&mut self, file: &Path, content: &str, facts: &Facts, conditional: bool) {
+        let mut blocks: Vec<Block> = Vec::new();
+
+        for (index, raw) in content.lines().enumerate() {
+            let line = raw.trim();
+
+            if line.starts_with('#') || line.is_empty() {
+                continue;
             }
-        } else if line.starts_with("ignore") {
-            match parse_ignore(line) {
-                Some(s) => {
-                    log::debug!("new bind command: {s}");
-                    types.push(s);
+
+            let indent = raw[..raw.len() - raw.trim_start().len()].chars().count();
+            let span = |column: usize| Span {
+                file: file.to_path_buf(),
+                line: index + 1,
+                column: indent + column + 1,
+            };
+            let active = blocks.last().is_none_or(|it| it.outer && it.taken);
+
+            let (statement, expanded) = match parse_line(line, &self.variables) {
+                Ok(Line {
+                    statement,
+                    expanded,
+                }) => (statement, expanded),
+                Err((column, error)) => {
+                    // a `set` the branch depends on may have been skipped as well
+                    if active || !matches!(error, ParseError::UndefinedVariable { .. }) {
+                        self.errors.push(ParseIssue::new(span(column), error));
+                    }
+                    // a broken `if` still opens a block for its `else` and
+                    // `endif`, and neither of its branches is taken
+                    if line.split_ascii_whitespace().next() == Some("if") {
+                        blocks.push(Block {
+                            span: span(0),
+                            outer: false,
+                            taken: false,
+                            seen_else: false,
+                        });
+                    }
+                    continue;
                 }
-                None => {
-                    log::debug!("failed to parse {line}");
+            };
+
+            match statement {
+                Statement::If(condition) => blocks.push(Block {
+                    span: span(0),
+                    outer: active,
+                    taken: active && condition.eval(facts),
+                    seen_else: false,
+                }),
+                Statement::Else => match blocks.last_mut() {
+                    Some(block) if !block.seen_else => {
+                        block.seen_else = true;
+                        block.taken = !block.taken;
+                    }
+                    _ => self.errors.push(ParseIssue::new(
+                        span(0),
+                        ParseError::UnexpectedBlock {
+                            keyword: "else".to_string(),
+                        },
+                    )),
+                },
+                Statement::EndIf => {
+                    if blocks.pop().is_none() {
+                        self.errors.push(ParseIssue::new(
+                            span(0),
+                            ParseError::UnexpectedBlock {
+                                keyword: "endif".to_string(),
+                            },
+                        ));
+                    }
                 }
-            }
-        } else if line.starts_with("file") || line.starts_with("add") {
-            match parse_file(line) {
-                Some(s) => {
-                    if FILES.lock().contains(&s) {
-                        log::warn!("detected same file, skip {line} for solving loop");
-                    } else {
-                        log::debug!("new file: {s}");
-                        FILES.lock().push(s.clone());
-                        match fs::read_to_string(&s) {
-                            Ok(s) => types.extend(parse(&s)),
-                            Err(e) => log::warn!("failed to read {s}: {e}"),
-                        }
+                _ if !active => {}
+                Statement::Set { name, value } => {
+                    log::debug!("new variable: {name}={value}");
+                    if let Err(error) = self.variables.set(name, value) {
+                        self.errors.push(ParseIssue::new(span(0), error));
                     }
                 }
-                _ => {
-                    log::debug!("failed to parse {line}");
+                Statement::Mount(kind) => {
+                    let kind = match &self.module {
+                        Some((_, dir)) => kind.relative_to(dir),
+                        None => kind,
+                    };
+                    log::debug!("new command: {kind}");
+                    self.commands.push(Command {
+                        span: span(0),
+                        kind,
+                        conditional: conditional || !blocks.is_empty(),
+                        expanded,
+                        module: self.module.as_ref().map(|(id, _)| id.clone()),
+                    });
+                }
+                Statement::Include(include) => {
+                    self.include_all(&span(0), &include, facts, conditional || !blocks.is_empty());
                 }
             }
         }
+
+        for block in blocks {
+            self.errors
+                .push(ParseIssue::new(block.span, ParseError::UnclosedIf));
+        }
+    }
+
+    /// Adds the list `module_id` ships in its directory. Its commands are owned
+    /// by the module, relative sources start from the module directory and
+    /// `set` variables do not leak in or out.
+    pub fn add_module_list(&mut self, module_id: &str, module_dir: &Path, facts: &Facts) {
+        let list = module_dir.join(defs::MODULE_LIST_FILE_NAME);
+        let builtins = self.variables.builtins();
+        let variables = mem::replace(&mut self.variables, builtins);
+        self.module = Some((module_id.to_string(), module_dir.to_path_buf()));
+        if let Err(e) = self.include(&list, facts, false) {
+            log::warn!("failed to parse list of {module_id}: {e}");
+        }
+        self.module = None;
+        self.variables = variables;
     }
 
-    types
+    /// The lenient view used at boot: bad lines are logged and dropped.
+    pub fn into_commands(self) -> Vec<Command> {
+        for issue in &self.errors {
+            log::debug!("failed to parse {}: {}", issue.span, issue.message);
+        }
+        for issue in &self.warnings {
+            log::debug!("{}: {}", issue.span, issue.message);
+        }
+
+        self.commands
+    }
 }
 
-fn parse_path(input: &str) -> String {
-    let first = input.as_bytes()[0] as char;
-    let last = input.as_bytes()[input.len() - 1] as char;
+/// The user's custom list followed by the lists of enabled modules.
+pub fn load_report(paths: &Paths, facts: &Facts) -> ParseReport {
+    let mut report = ParseReport::from_file_with(paths.custom_list(), facts, Variables::new(paths));
+    for (module_id, dir) in scanner::module_lists(&paths.module_dir) {
+        report.add_module_list(&module_id, &dir, facts);
+    }
+    report
+}
 
-    let strings = if (first == '\'' && last == '"') || (first == '"' && last == '\'') {
-        log::error!("mixed quotes detected in path: {input}");
-        String::new()
-    } else if (first == '\'' || first == '"') && first == last {
-        input[1..input.len() - 1].to_string()
+/// The commands of [`load_report`] for this device.
+pub fn load_commands(paths: &Paths) -> Vec<Command> {
+    load_report(paths, Facts::detect()).into_commands()
+}
+
+/// The files an include line names. Relative paths start from the including
+/// file's directory and globs expand in lexical order.
+pub fn include_paths(file: &Path, include: &str) -> Vec<PathBuf> {
+    let path = file
+        .parent()
+        .map_or_else(|| PathBuf::from(include), |dir| dir.join(include));
+    if is_glob(include) {
+        glob_paths(&path)
     } else {
-        input.to_string()
-    };
+        vec![path]
+    }
+}
 
-    strings.chars().filter(|c| !c.is_control()).collect()
+/// Variables every list starts with.
+const BUILTINS: [&str; 2] = ["MODULES", "CONFIG_DIR"];
+
+/// Values `${NAME}` expands to. `${MODULES}` and `${CONFIG_DIR}` are built in
+/// and follow the module and state directories in use, `${MODULE:<id>}` is a
+/// module's directory and `set NAME value` adds more.
+#[derive(Debug, Clone)]
+pub struct Variables {
+    values: FxHashMap<String, String>,
 }
 
-fn tokenize(input: &str) -> Vec<String> {
-    let mut tokens = Vec::new();
-    let mut current = String::new();
-    let mut in_quote: Option<char> = None;
-
-    for ch in input.chars() {
-        match in_quote {
-            Some(quote) => {
-                current.push(ch);
-                if ch == quote {
-                    in_quote = None;
+impl Default for Variables {
+    fn default() -> Self {
+        Self::new(&Paths::default())
+    }
+}
+
+impl Variables {
+    pub fn new(paths: &Paths) -> Self {
+        let values = [
+            ("MODULES", &paths.module_dir),
+            ("CONFIG_DIR", &paths.state_dir),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string_lossy().into_owned()))
+        .collect();
+        Self { values }
+    }
+
+    /// The built-in variables alone, what a module's list starts from.
+    fn builtins(&self) -> Self {
+        let values = self
+            .values
+            .iter()
+            .filter(|(name, _)| BUILTINS.contains(&name.as_str()))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        Self { values }
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        match name.split_once(':') {
+            Some(("MODULE", id)) if validate_module_id(id).is_ok() => {
+                self.values.get("MODULES").map(|dir| format!("{dir}/{id}"))
+            }
+            Some(_) => None,
+            None => self.values.get(name).cloned(),
+        }
+    }
+
+    /// Defines a `set` variable; built-in names cannot be redefined.
+    pub fn set(&mut self, name: String, value: String) -> Result<(), ParseError> {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid || BUILTINS.contains(&name.as_str()) || name == "MODULE" {
+            return Err(ParseError::InvalidVariable { name });
+        }
+
+        self.values.insert(name, value);
+        Ok(())
+    }
+}
+
+/// Splits a line into shell-style words.
+///
+/// Outside quotes a backslash escapes the next character. Single quotes keep
+/// everything literally; inside double quotes only `\\`, `\"`, `\$` and
+/// `` \` `` are escapes. `${NAME}` expands outside single quotes. Adjacent
+/// quoted and unquoted parts join into one word, and control characters are
+/// dropped. A word starting with `#` begins a comment.
+struct Lexer<'a> {
+    input: &'a str,
+    chars: std::iter::Peekable<std::iter::Enumerate<std::str::Chars<'a>>>,
+    variables: &'a Variables,
+    /// Whether any variable was expanded.
+    expanded: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str, variables: &'a Variables) -> Self {
+        Self {
+            input,
+            chars: input.chars().enumerate().peekable(),
+            variables,
+            expanded: false,
+        }
+    }
+
+    /// The raw text from `column` to the end of the line, for error messages.
+    fn rest(&self, column: usize) -> String {
+        self.input.chars().skip(column).collect()
+    }
+
+    /// Expands `${NAME}` after a `$` at `start`; a lone `$` stays as it is.
+    fn variable(&mut self, start: usize, word: &mut String) -> Result<(), (usize, ParseError)> {
+        if self.chars.next_if(|(_, ch)| *ch == '{').is_none() {
+            word.push('$');
+            return Ok(());
+        }
+
+        let mut name = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '}')) => break,
+                Some((_, ch)) => name.push(ch),
+                None => {
+                    return Err((
+                        start,
+                        ParseError::UnclosedVariable {
+                            token: self.rest(start),
+                        },
+                    ));
                 }
             }
-            None => {
-                if ch == '\'' || ch == '"' {
-                    in_quote = Some(ch);
-                    current.push(ch);
-                } else if ch.is_ascii_whitespace() {
-                    if !current.is_empty() {
-                        tokens.push(std::mem::take(&mut current));
+        }
+
+        let value = self
+            .variables
+            .get(&name)
+            .ok_or((start, ParseError::UndefinedVariable { name }))?;
+        word.push_str(&value);
+        self.expanded = true;
+        Ok(())
+    }
+
+    fn quoted(
+        &mut self,
+        quote: char,
+        start: usize,
+        word: &mut String,
+    ) -> Result<(), (usize, ParseError)> {
+        while let Some((column, ch)) = self.chars.next() {
+            match ch {
+                _ if ch == quote => return Ok(()),
+                '\\' if quote == '"' => match self.chars.peek() {
+                    Some(&(_, next @ ('\\' | '"' | '$' | '`'))) => {
+                        self.chars.next();
+                        word.push(next);
                     }
-                } else {
-                    current.push(ch);
-                }
+                    _ => word.push(ch),
+                },
+                '$' if quote == '"' => self.variable(column, word)?,
+                _ if ch.is_control() => {}
+                _ => word.push(ch),
             }
         }
+
+        let token = self.rest(start);
+        let other = if quote == '"' { '\'' } else { '"' };
+        if token.trim_end().ends_with(other) {
+            Err((start, ParseError::MixedQuotes { token }))
+        } else {
+            Err((start, ParseError::UnbalancedQuote { token }))
+        }
     }
-    if !current.is_empty() {
-        tokens.push(current);
+
+    /// The next word and the column it starts at. With `split` unset the
+    /// whole remaining input is one word.
+    fn word(&mut self, split: bool) -> Result<Option<(usize, String)>, (usize, ParseError)> {
+        while self
+            .chars
+            .next_if(|(_, ch)| split && ch.is_ascii_whitespace())
+            .is_some()
+        {}
+
+        let Some(&(start, first)) = self.chars.peek() else {
+            return Ok(None);
+        };
+        if split && first == '#' {
+            // the rest of the line is a comment
+            self.chars.by_ref().for_each(drop);
+            return Ok(None);
+        }
+
+        let mut word = String::new();
+        while let Some(&(column, ch)) = self.chars.peek() {
+            if split && ch.is_ascii_whitespace() {
+                break;
+            }
+            self.chars.next();
+            match ch {
+                '\'' | '"' => self.quoted(ch, column, &mut word)?,
+                '\\' => {
+                    if let Some((_, next)) = self.chars.next()
+                        && !next.is_control()
+                    {
+                        word.push(next);
+                    }
+                }
+                '$' => self.variable(column, &mut word)?,
+                _ if ch.is_control() => {}
+                _ => word.push(ch),
+            }
+        }
+
+        Ok(Some((start, word)))
     }
-    tokens
 }
 
-fn parse_bind(input: &str) -> Option<MountType> {
-    let tokens = tokenize(input);
+/// Quotes `path` so the lexer reads it back unchanged. Paths made only of
+/// characters that are never special are left as they are.
+pub fn quote_path(path: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-+:@%,=~".contains(c);
+    if !path.is_empty() && path.chars().all(plain) && !path.starts_with('~') {
+        return path.to_string();
+    }
 
-    if tokens.len() < 3 || tokens[0] != "bind" {
-        return None;
+    let mut quoted = String::with_capacity(path.len() + 2);
+    quoted.push('"');
+    for ch in path.chars() {
+        if matches!(ch, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
     }
-    let source = parse_path(&tokens[1]);
-    let target = parse_path(&tokens[2]);
-    if source.is_empty() || target.is_empty() {
-        log::debug!("missing source/target, skip");
-        None
-    } else {
-        Some(MountType::Mount { source, target })
+    quoted.push('"');
+    quoted
+}
+
+/// A parsed line and whether it expanded any variable.
+pub struct Line {
+    pub statement: Statement,
+    pub expanded: bool,
+}
+
+/// Parses one non-comment line; errors carry the 0-based column they point at.
+pub fn parse_line(input: &str, variables: &Variables) -> Result<Line, (usize, ParseError)> {
+    let mut lexer = Lexer::new(input, variables);
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.word(true)? {
+        tokens.push(token);
     }
+
+    Ok(Line {
+        statement: statement(input, &tokens)?,
+        expanded: lexer.expanded,
+    })
 }
 
-fn parse_ignore(input: &str) -> Option<MountType> {
-    let tokens = tokenize(input);
-    if tokens.len() < 2 || tokens[0] != "ignore" {
-        return None;
+fn statement(input: &str, tokens: &[(usize, String)]) -> Result<Statement, (usize, ParseError)> {
+    let Some((_, verb)) = tokens.first() else {
+        return Err((
+            0,
+            ParseError::UnknownCommand {
+                verb: String::new(),
+            },
+        ));
+    };
+
+    match verb.as_str() {
+        "if" => {
+            return Condition::parse(&tokens[1..], input.chars().count()).map(Statement::If);
+        }
+        "else" => return Ok(Statement::Else),
+        "endif" => return Ok(Statement::EndIf),
+        _ => {}
     }
-    let source = parse_path(&tokens[1]);
-    if source.is_empty() {
-        log::debug!("missing source, skip");
-        None
+
+    let mut tokens = tokens.to_vec();
+    let options = if verb == "bind" {
+        BindOptions::take(&mut tokens)?
     } else {
-        Some(MountType::Ignore { source })
+        BindOptions::default()
+    };
+
+    let arguments: &[&str] = match verb.as_str() {
+        "bind" => &["source", "target"],
+        "ignore" => &["source"],
+        "ignore-re" => &["pattern"],
+        "ignore-target" | "hide" | "replace" | "mkdir" | "tmpfs" => &["target"],
+        "symlink" => &["link", "target"],
+        "file" | "add" => &["path"],
+        "set" => &["name", "value"],
+        _ => {
+            return Err((0, ParseError::UnknownCommand { verb: verb.clone() }));
+        }
+    };
+
+    let mut values = Vec::new();
+    for (index, argument) in arguments.iter().enumerate() {
+        let value = match tokens.get(index + 1) {
+            Some((_, token)) => token.clone(),
+            None => String::new(),
+        };
+        if value.is_empty() {
+            let column = tokens
+                .get(index + 1)
+                .map_or_else(|| input.chars().count(), |it| it.0);
+            return Err((
+                column,
+                ParseError::MissingArgument {
+                    command: verb.clone(),
+                    argument,
+                },
+            ));
+        }
+        values.push(value);
     }
+
+    let mut values = values.into_iter();
+    let mut next = || values.next().unwrap_or_default();
+    Ok(match verb.as_str() {
+        "bind" => Statement::Mount(MountType::Mount {
+            source: next(),
+            target: next(),
+            options,
+        }),
+        "ignore" => Statement::Mount(MountType::Ignore { source: next() }),
+        "ignore-re" => Statement::Mount(MountType::IgnoreRe { pattern: next() }),
+        "ignore-target" => Statement::Mount(MountType::IgnoreTarget { target: next() }),
+        "hide" => Statement::Mount(MountType::Hide { target: next() }),
+        "replace" => Statement::Mount(MountType::Replace { target: next() }),
+        "symlink" => Statement::Mount(MountType::Symlink {
+            link: next(),
+            target: next(),
+        }),
+        "mkdir" | "tmpfs" => Statement::Mount(creation(
+            verb,
+            next(),
+            tokens.get(arguments.len() + 1..).unwrap_or_default(),
+        )?),
+        "set" => Statement::Set {
+            name: next(),
+            value: next(),
+        },
+        _ => Statement::Include(next()),
+    })
 }
 
-fn parse_file(input: &str) -> Option<String> {
-    let tokens = tokenize(input);
-    if tokens.len() < 2 || (tokens[0] != "file" && tokens[0] != "add") {
-        return None;
-    }
-    let path = parse_path(&tokens[1]);
-    if path.is_empty() {
-        log::debug!("missing path, skip");
-        None
-    } else {
-        Some(path)
+/// Builds a `mkdir` or `tmpfs` directive from its optional arguments.
+fn creation(
+    verb: &str,
+    target: String,
+    extra: &[(usize, String)],
+) -> Result<MountType, (usize, ParseError)> {
+    let invalid = |(column, value): &(usize, String), argument| {
+        (
+            *column,
+            ParseError::InvalidArgument {
+                command: verb.to_string(),
+                argument,
+                value: value.clone(),
+            },
+        )
+    };
+    let number = |index: usize, argument, radix, max| {
+        extra
+            .get(index)
+            .map(|token| {
+                u32::from_str_radix(&token.1, radix)
+                    .ok()
+                    .filter(|it| *it <= max)
+                    .ok_or_else(|| invalid(token, argument))
+            })
+            .transpose()
+    };
+
+    if verb == "mkdir" {
+        return Ok(MountType::Mkdir {
+            target,
+            mode: number(0, "mode", 8, 0o7777)?,
+            uid: number(1, "uid", 10, u32::MAX)?,
+            gid: number(2, "gid", 10, u32::MAX)?,
+            context: extra.get(3).map(|it| it.1.clone()),
+        });
     }
+
+    // a number of bytes, with an optional k, m, g or % suffix
+    let size = extra
+        .first()
+        .map(|token| {
+            let digits = token
+                .1
+                .trim_end_matches(['k', 'K', 'm', 'M', 'g', 'G', '%']);
+            if !digits.is_empty()
+                && digits.len() + 1 >= token.1.len()
+                && digits.chars().all(|it| it.is_ascii_digit())
+            {
+                Ok(token.1.clone())
+            } else {
+                Err(invalid(token, "size"))
+            }
+        })
+        .transpose()?;
+    Ok(MountType::Tmpfs { target, size })
 }
 
 #[cfg(test)]
@@ -0,0 +1,122 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+
+use crate::defs;
+
+/// Where the engine finds modules and keeps its own files. Every location can
+/// be moved with a flag or an environment variable, flags winning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paths {
+    pub module_dir: PathBuf,
+    pub config_file: PathBuf,
+    /// The custom list, module rules, mount status and reports live here.
+    pub state_dir: PathBuf,
+    /// The tmpfs the mirrored directories are built in.
+    pub work_root: PathBuf,
+}
+
+/// The path flags, shared by every command. Each falls back to its
+/// environment variable.
+#[derive(Debug, Default, Args)]
+pub struct PathArgs {
+    /// Where modules are installed
+    #[arg(long, global = true, value_name = "DIR")]
+    pub module_dir: Option<PathBuf>,
+    /// The config file, config.toml in the state directory by default
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Where the custom list, rules, status and reports live
+    #[arg(long, global = true, value_name = "DIR")]
+    pub state_dir: Option<PathBuf>,
+    /// Where the tmpfs work directory is mounted
+    #[arg(long, global = true, value_name = "DIR")]
+    pub work_root: Option<PathBuf>,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Self {
+            module_dir: PathBuf::from(defs::MODULE_PATH),
+            config_file: Path::new(defs::CONFIG_DIR).join(defs::CONFIG_FILE_NAME),
+            state_dir: PathBuf::from(defs::CONFIG_DIR),
+            work_root: PathBuf::from(defs::WORK_ROOT),
+        }
+    }
+}
+
+impl Paths {
+    /// Resolves `args`, filling what they leave out from the environment.
+    pub fn from_args(args: PathArgs) -> Self {
+        Self::resolve(args, |name| env::var_os(name))
+    }
+
+    fn resolve<F>(args: PathArgs, env: F) -> Self
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let lookup = |flag: Option<PathBuf>, variable: &str| {
+            flag.filter(|it| !it.as_os_str().is_empty())
+                .or_else(|| env(variable).filter(|it| !it.is_empty()).map(PathBuf::from))
+        };
+
+        let defaults = Self::default();
+        let state_dir =
+            lookup(args.state_dir, "MAGIC_MOUNT_STATE_DIR").unwrap_or(defaults.state_dir);
+        Self {
+            module_dir: lookup(args.module_dir, "MAGIC_MOUNT_MODULE_DIR")
+                .unwrap_or(defaults.module_dir),
+            // a moved state directory brings its config along unless told otherwise
+            config_file: lookup(args.config, "MAGIC_MOUNT_CONFIG")
+                .unwrap_or_else(|| state_dir.join(defs::CONFIG_FILE_NAME)),
+            state_dir,
+            work_root: lookup(args.work_root, "MAGIC_MOUNT_WORK_ROOT")
+                .unwrap_or(defaults.work_root),
+        }
+    }
+
+    pub fn custom_list(&self) -> PathBuf {
+        self.state_dir.join(defs::CUSTOM_LIST_FILE_NAME)
+    }
+
+    pub fn rules_dir(&self) -> PathBuf {
+        self.state_dir.join(defs::MODULE_RULES_DIR_NAME)
+    }
+
+    pub fn scanned_list(&self) -> PathBuf {
+        self.state_dir.join(defs::SCANNED_LIST_FILE_NAME)
+    }
+
+    pub fn mount_status(&self) -> PathBuf {
+        self.state_dir.join(defs::MOUNT_STATUS_FILE_NAME)
+    }
+
+    pub fn last_run_report(&self) -> PathBuf {
+        self.state_dir.join(defs::LAST_RUN_REPORT_FILE_NAME)
+    }
+
+    pub fn umount_list(&self) -> PathBuf {
+        self.state_dir.join(defs::UMOUNT_LIST_FILE_NAME)
+    }
+
+    /// The `module.prop` of this module, whose description shows the mount count.
+    pub fn module_prop(&self) -> PathBuf {
+        self.module_dir.join(env!("MODULE_ID")).join("module.prop")
+    }
+
+    /// Where the mirrored directories are staged before being moved in place.
+    pub fn work_dir(&self) -> PathBuf {
+        self.work_root.join("workdir")
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/unit/paths.rs"]
+mod tests;
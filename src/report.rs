@@ -0,0 +1,126 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    fs,
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::Output, config::Config, errors::Result, magic_mount::MountStats, utils::ksucalls,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PhaseError {
+    pub phase: String,
+    /// The error followed by its causes, outermost first.
+    pub chain: Vec<String>,
+}
+
+/// A machine readable summary of one boot, written to `last_run.json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    /// Unix time in milliseconds.
+    pub started_at: u128,
+    pub finished_at: u128,
+    pub duration_ms: u128,
+    pub phases: Vec<Phase>,
+    pub counts: MountStats,
+    pub errors: Vec<PhaseError>,
+    pub kernel: Option<String>,
+    pub ksu_version: Option<String>,
+    pub config: Config,
+    #[serde(skip)]
+    start: Option<Instant>,
+}
+
+fn unix_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_millis())
+}
+
+impl Report {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            started_at: unix_millis(),
+            finished_at: 0,
+            duration_ms: 0,
+            phases: Vec::new(),
+            counts: MountStats::default(),
+            errors: Vec::new(),
+            kernel: fs::read_to_string("/proc/sys/kernel/osrelease")
+                .ok()
+                .map(|it| it.trim().to_string()),
+            ksu_version: ksucalls::version(),
+            config: config.clone(),
+            start: Some(Instant::now()),
+        }
+    }
+
+    /// Runs `f` as the phase `name`, recording how long it took.
+    pub fn time<T, F>(&mut self, name: &str, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let ret = f();
+        self.phases.push(Phase {
+            name: name.to_string(),
+            duration_ms: start.elapsed().as_millis(),
+        });
+        ret
+    }
+
+    pub fn error(&mut self, phase: &str, e: &anyhow::Error) {
+        self.errors.push(PhaseError {
+            phase: phase.to_string(),
+            chain: e.chain().map(ToString::to_string).collect(),
+        });
+    }
+
+    pub fn finish(&mut self, counts: MountStats) {
+        self.counts = counts;
+        self.finished_at = unix_millis();
+        self.duration_ms = self.start.map_or(0, |it| it.elapsed().as_millis());
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .context("failed to write boot report")?;
+        Ok(())
+    }
+
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let content =
+            fs::read_to_string(path).context("no boot report, has magic mount run yet?")?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
+
+pub fn handle_report<P>(path: P, output: Output) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    output.json(&Report::load(path)?)
+}
+
+#[cfg(test)]
+#[path = "../tests/unit/report.rs"]
+mod tests;
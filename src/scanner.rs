@@ -1,13 +1,26 @@
 // Copyright (C) 2026 meta-magic_mount-rs developers
 // SPDX-License-Identifier: GPL-v3
 
-use std::{collections::BTreeMap, fs, io::Cursor, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
 
+use anyhow::Context;
 use java_properties::PropertiesIter;
 use rustc_hash::{FxHashMap, FxHashSet};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-use crate::{defs, errors::Result, utils::validate_module_id};
+use crate::{
+    cli::{Output, RulesAction},
+    defs,
+    errors::Result,
+    magic_mount::status::{self, ModuleStatus},
+    utils::validate_module_id,
+};
 
 #[derive(Debug)]
 struct ModuleRecord {
@@ -22,12 +35,106 @@ struct ModuleRecord {
     source_path: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MountMode {
+    #[default]
+    Magic,
+    Ignore,
+}
+
+/// How a module is mounted, persisted as `<rules dir>/<module id>.json`.
+///
+/// `paths` and `contexts` keys are relative to the module root, e.g.
+/// `system/etc/hosts`, and apply to everything below them; the longest
+/// matching key wins.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct ModuleRules {
-    default_mode: String,
-    paths: BTreeMap<String, String>,
+    pub default_mode: MountMode,
+    pub paths: BTreeMap<String, MountMode>,
+    /// Labels module files get before they are mounted.
+    pub contexts: BTreeMap<String, String>,
+}
+
+/// The value of the longest key in `map` that `path` starts with.
+fn longest_match<'a, T>(map: &'a BTreeMap<String, T>, path: &Path) -> Option<&'a T> {
+    map.iter()
+        .map(|(key, value)| (Path::new(key.trim_matches('/')), value))
+        .filter(|(key, _)| path.starts_with(key))
+        .max_by_key(|(key, _)| key.components().count())
+        .map(|(_, value)| value)
+}
+
+impl ModuleRules {
+    fn path<P>(rules_dir: P, module_id: &str) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        rules_dir.as_ref().join(format!("{module_id}.json"))
+    }
+
+    pub fn load<P>(rules_dir: P, module_id: &str) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let path = Self::path(rules_dir, module_id);
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("invalid module rules {}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save<P>(&self, rules_dir: P, module_id: &str) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let rules_dir = rules_dir.as_ref();
+        fs::create_dir_all(rules_dir).context("failed to create module rules directory")?;
+        fs::write(
+            Self::path(rules_dir, module_id),
+            serde_json::to_string_pretty(self)?,
+        )
+        .context("failed to write module rules")?;
+        Ok(())
+    }
+
+    /// The mode of `path`, relative to the module root.
+    pub fn mode_for<P>(&self, path: P) -> MountMode
+    where
+        P: AsRef<Path>,
+    {
+        longest_match(&self.paths, path.as_ref()).map_or(self.default_mode, |mode| *mode)
+    }
+
+    /// The label for `path`, relative to the module root, if one is set.
+    pub fn context_for<P>(&self, path: P) -> Option<&str>
+    where
+        P: AsRef<Path>,
+    {
+        longest_match(&self.contexts, path.as_ref()).map(String::as_str)
+    }
+
+    /// Whether nothing at or below `path` should be mounted.
+    pub fn ignores<P>(&self, path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        self.mode_for(path) == MountMode::Ignore
+            && !self.paths.iter().any(|(key, mode)| {
+                *mode == MountMode::Magic && Path::new(key.trim_matches('/')).starts_with(path)
+            })
+    }
 }
 
+/// The version of the `AppModule` shape, bumped like `CONFIG_SCHEMA`.
+pub const MODULE_SCHEMA: u32 = 1;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AppModule {
     pub id: String,
@@ -35,14 +142,16 @@ pub struct AppModule {
     version: String,
     author: String,
     description: String,
-    mode: String,
+    mode: MountMode,
     is_mounted: bool,
     enabled: bool,
     source_path: String,
     rules: ModuleRules,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<ModuleStatus>,
 }
 
-fn read_prop<P>(path: P) -> Result<FxHashMap<String, String>>
+pub fn read_prop<P>(path: P) -> Result<FxHashMap<String, String>>
 where
     P: AsRef<Path>,
 {
@@ -137,38 +246,53 @@ where
     modules
 }
 
-pub fn show_modules(modules: Vec<AppModule>) -> Result<Vec<AppModule>> {
-    let orgi = std::fs::read_to_string(defs::SCANNED_LIST)?;
-    let orgi_modules: Vec<AppModule> = serde_json::from_str(&orgi)?;
-
-    let mounted_modules: Vec<String> = orgi_modules
-        .iter()
-        .filter(|s| s.is_mounted)
-        .map(|s| s.name.clone())
-        .collect();
-
-    let mut ret = Vec::new();
+/// Attaches what the last boot actually mounted for each module.
+pub fn show_modules<P>(modules: Vec<AppModule>, status_path: P) -> Result<Vec<AppModule>>
+where
+    P: AsRef<Path>,
+{
+    let mut status = status::load(status_path)?;
 
-    for mut i in modules {
-        if mounted_modules.contains(&i.name) {
-            i.is_mounted = true;
-        }
-        ret.push(i);
-    }
+    Ok(modules
+        .into_iter()
+        .map(|mut module| {
+            module.status = status.remove(&module.id);
+            module.is_mounted = module.status.as_ref().is_some_and(ModuleStatus::is_mounted);
+            module
+        })
+        .collect())
+}
 
-    Ok(ret)
+/// The ids and directories of enabled modules that ship a custom list.
+pub fn module_lists<P>(module_dir: P) -> Vec<(String, PathBuf)>
+where
+    P: AsRef<Path>,
+{
+    collect_modules(module_dir, &[])
+        .into_iter()
+        .filter(|module| !module.disabled && !module.skip_mount)
+        .map(|module| (module.id, PathBuf::from(module.source_path)))
+        .filter(|(_, dir)| dir.join(defs::MODULE_LIST_FILE_NAME).is_file())
+        .collect()
 }
 
-pub fn list_modules<P>(module_dir: P, extra: &[String]) -> Vec<AppModule>
+pub fn list_modules<P>(module_dir: P, rules_dir: P, extra: &[String]) -> Vec<AppModule>
 where
     P: AsRef<Path>,
 {
     collect_modules(module_dir, extra)
         .into_iter()
         .map(|module| {
-            let is_mounted = module.has_mount_files && !module.disabled && !module.skip_mount;
-            let mode = if is_mounted { "magic" } else { "ignore" }.to_string();
-            let default_mode = mode.clone();
+            let rules = ModuleRules::load(&rules_dir, &module.id);
+            let is_mounted = module.has_mount_files
+                && !module.disabled
+                && !module.skip_mount
+                && !rules.ignores("system");
+            let mode = if is_mounted {
+                MountMode::Magic
+            } else {
+                MountMode::Ignore
+            };
 
             AppModule {
                 id: module.id,
@@ -180,14 +304,36 @@ where
                 is_mounted,
                 enabled: !module.disabled,
                 source_path: module.source_path,
-                rules: ModuleRules {
-                    default_mode,
-                    paths: BTreeMap::new(),
-                },
+                rules,
+                status: None,
             }
         })
         .collect()
 }
+
+/// `module-rules get <id>` / `module-rules set <id> <payload>`
+pub fn handle_module_rules<P>(action: &RulesAction, rules_dir: P, output: Output) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    match action {
+        RulesAction::Get { module_id } => {
+            validate_module_id(module_id)?;
+            let rules = ModuleRules::load(&rules_dir, module_id);
+            output.json(&rules)?;
+        }
+        RulesAction::Set { module_id, payload } => {
+            validate_module_id(module_id)?;
+            let rules: ModuleRules = serde_json::from_str(&payload.read()?)
+                .context("failed to parse module rules payload json")?;
+            rules.save(&rules_dir, module_id)?;
+            output.json(&json!({ "ok": true }))?;
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 #[path = "../tests/unit/scanner.rs"]
 mod tests;
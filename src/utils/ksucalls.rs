@@ -27,6 +27,10 @@ pub fn check_ksu() {
     KSU.store(status, std::sync::atomic::Ordering::Relaxed);
 }
 
+pub fn version() -> Option<String> {
+    ksu::version().map(|v| v.to_string())
+}
+
 pub fn send_unmountable<P>(target: P)
 where
     P: AsRef<Path>,
@@ -11,12 +11,12 @@ use std::{
 };
 
 use anyhow::Context;
-use extattr::{Flags as XattrFlags, lgetxattr, lsetxattr};
 use regex_lite::Regex;
 
 use crate::{
     defs,
     errors::{Error, Result},
+    mount_backend::MountBackend,
     utils::ksucalls::KSU,
 };
 
@@ -36,27 +36,31 @@ pub fn validate_module_id(module_id: &str) -> Result<()> {
     }
 }
 
-pub fn lsetfilecon<P: AsRef<Path>>(path: P, con: &str) -> Result<()> {
+pub fn lsetfilecon<P: AsRef<Path>>(backend: &dyn MountBackend, path: P, con: &str) -> Result<()> {
     log::debug!("file: {},con: {}", path.as_ref().display(), con);
-    lsetxattr(&path, defs::SELINUX_XATTR, con, XattrFlags::empty()).with_context(|| {
-        format!(
-            "Failed to change SELinux context for {}",
-            path.as_ref().display()
-        )
-    })?;
+    backend
+        .setxattr(path.as_ref(), defs::SELINUX_XATTR, con.as_bytes())
+        .with_context(|| {
+            format!(
+                "Failed to change SELinux context for {}",
+                path.as_ref().display()
+            )
+        })?;
     Ok(())
 }
 
-pub fn lgetfilecon<P>(path: P) -> Result<String>
+pub fn lgetfilecon<P>(backend: &dyn MountBackend, path: P) -> Result<String>
 where
     P: AsRef<Path>,
 {
-    let con = lgetxattr(&path, defs::SELINUX_XATTR).with_context(|| {
-        format!(
-            "Failed to get SELinux context for {}",
-            path.as_ref().display()
-        )
-    })?;
+    let con = backend
+        .getxattr(path.as_ref(), defs::SELINUX_XATTR)
+        .with_context(|| {
+            format!(
+                "Failed to get SELinux context for {}",
+                path.as_ref().display()
+            )
+        })?;
     let con = String::from_utf8_lossy(&con);
     Ok(con.to_string())
 }
@@ -75,8 +79,8 @@ where
     }
 }
 
-fn legacy_update_desc<S: ToString>(desc: &S) -> Result<()> {
-    let prop = fs::read_to_string(defs::MODULE_PROP)?;
+fn legacy_update_desc<S: ToString>(module_prop: &Path, desc: &S) -> Result<()> {
+    let prop = fs::read_to_string(module_prop)?;
     let mut temp = tempfile::Builder::new().tempfile()?;
 
     let new: Vec<String> = prop
@@ -94,12 +98,15 @@ fn legacy_update_desc<S: ToString>(desc: &S) -> Result<()> {
         .write_all(new.join("\n").as_bytes())
         .map_err(|e| log::error!("Failed to update description: {e}"));
 
-    fs::rename(temp.path(), defs::MODULE_PROP)?;
+    fs::rename(temp.path(), module_prop)?;
 
     Ok(())
 }
 
-pub fn update_desc(files: u32, symbols: u32) -> Result<()> {
+pub fn update_desc<P>(module_prop: P, files: u32, symbols: u32) -> Result<()>
+where
+    P: AsRef<Path>,
+{
     let text = format!(
         "[😋 mount files/symbol {}] An implementation of a metamodule using Magic Mount.",
         files + symbols
@@ -133,7 +140,7 @@ pub fn update_desc(files: u32, symbols: u32) -> Result<()> {
             "failed to set module config override.description: {}, fallback to write regular file",
             String::from_utf8_lossy(&output.stderr)
         );
-        legacy_update_desc(&text)?;
+        legacy_update_desc(module_prop.as_ref(), &text)?;
     }
 
     Ok(())
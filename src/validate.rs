@@ -0,0 +1,349 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use regex_lite::Regex;
+use serde::Serialize;
+
+use crate::{
+    cli::Output,
+    condition::Facts,
+    config::{CONFIG_KEYS, Config},
+    errors::{Error, Result},
+    ignore::{glob_to_regex, is_glob},
+    magic_mount::utils::{BUILTIN_PARTITIONS, in_partition_root},
+    parser::{MountType, ParseReport, load_report},
+    paths::Paths,
+    utils::validate_module_id,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, ": {severity}: {}", self.message)
+    }
+}
+
+#[derive(Default)]
+struct Diagnostics {
+    file: PathBuf,
+    list: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    fn push<S>(&mut self, line: Option<usize>, severity: Severity, message: S)
+    where
+        S: Into<String>,
+    {
+        self.list.push(Diagnostic {
+            file: self.file.clone(),
+            line,
+            severity,
+            message: message.into(),
+        });
+    }
+
+    fn error<S: Into<String>>(&mut self, line: usize, message: S) {
+        self.push(Some(line), Severity::Error, message);
+    }
+
+    fn warning<S: Into<String>>(&mut self, line: usize, message: S) {
+        self.push(Some(line), Severity::Warning, message);
+    }
+}
+
+/// The 1-based line of `key = ...` in `content`, if there is one.
+fn key_line(content: &str, key: &str) -> Option<usize> {
+    content
+        .lines()
+        .position(|line| {
+            line.split_once('=')
+                .is_some_and(|(k, _)| k.trim().trim_matches('"') == key)
+        })
+        .map(|it| it + 1)
+}
+
+pub fn validate_config<P>(path: P) -> Vec<Diagnostic>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let mut diagnostics = Diagnostics {
+        file: path.to_path_buf(),
+        ..Diagnostics::default()
+    };
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        // the defaults are used, as at boot
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return diagnostics.list,
+        Err(e) => {
+            diagnostics.push(None, Severity::Error, format!("cannot read: {e}"));
+            return diagnostics.list;
+        }
+    };
+
+    let table: toml::Table = match toml::from_str(&content) {
+        Ok(table) => table,
+        Err(e) => {
+            let line = e
+                .span()
+                .map(|span| content[..span.start].lines().count().max(1));
+            diagnostics.push(line, Severity::Error, e.message().to_string());
+            return diagnostics.list;
+        }
+    };
+
+    for key in table.keys() {
+        if !CONFIG_KEYS.contains(&key.as_str()) {
+            diagnostics.push(
+                key_line(&content, key),
+                Severity::Warning,
+                format!("unknown key {key:?} is kept but ignored"),
+            );
+        }
+    }
+
+    let (config, errors) = Config::from_table(&table);
+    for error in errors {
+        diagnostics.push(
+            key_line(&content, error.key),
+            Severity::Error,
+            format!("{error}, the default is used"),
+        );
+    }
+
+    let mountsource_line = key_line(&content, "mountsource");
+    if config.mountsource.is_empty() {
+        diagnostics.push(mountsource_line, Severity::Error, "mountsource is empty");
+    } else if config
+        .mountsource
+        .contains(|c: char| c.is_whitespace() || c.is_control() || c == ',' || c == '/')
+    {
+        diagnostics.push(
+            mountsource_line,
+            Severity::Error,
+            format!("invalid mountsource {:?}", config.mountsource),
+        );
+    }
+
+    let partition_re = Regex::new(r"^[a-z0-9_]+$").unwrap();
+    let partitions_line = key_line(&content, "partitions");
+    for partition in &config.partitions {
+        if !partition_re.is_match(partition) {
+            diagnostics.push(
+                partitions_line,
+                Severity::Error,
+                format!("invalid partition name {partition:?}"),
+            );
+        } else if partition == "system" {
+            diagnostics.push(
+                partitions_line,
+                Severity::Warning,
+                "\"system\" is always mounted and does not need to be listed",
+            );
+        }
+    }
+
+    let priority_line = key_line(&content, "priority");
+    for id in &config.priority {
+        if validate_module_id(id).is_err() {
+            diagnostics.push(
+                priority_line,
+                Severity::Warning,
+                format!("invalid module id {id:?} in priority"),
+            );
+        }
+    }
+
+    diagnostics.list
+}
+
+fn check_absolute(diagnostics: &mut Diagnostics, line: usize, path: &str) -> bool {
+    if path.starts_with('/') {
+        true
+    } else {
+        diagnostics.error(line, format!("{path:?} is not an absolute path"));
+        false
+    }
+}
+
+struct ListValidator<'a> {
+    partitions: &'a [String],
+}
+
+impl ListValidator<'_> {
+    fn target_allowed(&self, target: &str) -> bool {
+        let Some(partition) = Path::new(target)
+            .strip_prefix("/")
+            .ok()
+            .and_then(|it| it.iter().next())
+            .and_then(|it| it.to_str())
+        else {
+            return false;
+        };
+
+        partition == "system"
+            || BUILTIN_PARTITIONS.iter().any(|(p, _)| *p == partition)
+            || self.partitions.iter().any(|p| p == partition)
+    }
+
+    fn check_target(&self, diagnostics: &mut Diagnostics, line: usize, target: &str) {
+        if check_absolute(diagnostics, line, target) && !self.target_allowed(target) {
+            diagnostics.error(
+                line,
+                format!("target {target} is outside the allowed partitions"),
+            );
+        }
+    }
+
+    fn command(&self, diagnostics: &mut Diagnostics, line: usize, kind: &MountType) {
+        match kind {
+            MountType::Mount {
+                source,
+                target,
+                options,
+            } => {
+                if check_absolute(diagnostics, line, source) && !Path::new(source).exists() {
+                    let message = format!("source {source} does not exist");
+                    if options.optional {
+                        diagnostics.warning(line, message);
+                    } else {
+                        diagnostics.error(line, message);
+                    }
+                }
+                self.check_target(diagnostics, line, target);
+            }
+            MountType::Ignore { source } if is_glob(source) => {
+                if check_absolute(diagnostics, line, source)
+                    && let Err(e) = Regex::new(&glob_to_regex(source))
+                {
+                    diagnostics.error(line, format!("invalid glob {source}: {e}"));
+                }
+            }
+            MountType::Ignore { source } => {
+                if check_absolute(diagnostics, line, source) && !Path::new(source).exists() {
+                    diagnostics.warning(line, format!("source {source} does not exist"));
+                }
+            }
+            MountType::IgnoreTarget { target } => {
+                if check_absolute(diagnostics, line, target)
+                    && is_glob(target)
+                    && let Err(e) = Regex::new(&glob_to_regex(target))
+                {
+                    diagnostics.error(line, format!("invalid glob {target}: {e}"));
+                }
+            }
+            MountType::Replace { target } => self.check_target(diagnostics, line, target),
+            MountType::Hide { target }
+            | MountType::Symlink { target, .. }
+            | MountType::Mkdir { target, .. }
+            | MountType::Tmpfs { target, .. } => {
+                self.check_target(diagnostics, line, target);
+                if self.target_allowed(target)
+                    && in_partition_root(Path::new(target), self.partitions)
+                {
+                    diagnostics.error(
+                        line,
+                        format!("target {target} is directly in a partition root"),
+                    );
+                }
+            }
+            MountType::IgnoreRe { pattern } => {
+                if let Err(e) = Regex::new(pattern) {
+                    diagnostics.error(line, format!("invalid regex {pattern}: {e}"));
+                }
+            }
+        }
+    }
+}
+
+/// Diagnostics for a parsed list: its parse errors and warnings and the
+/// problems of the commands in effect, ordered by file and line.
+pub fn validate_list(report: &ParseReport, partitions: &[String]) -> Vec<Diagnostic> {
+    let validator = ListValidator { partitions };
+    let mut diagnostics = Vec::new();
+    let issues = report
+        .errors
+        .iter()
+        .map(|it| (it, Severity::Error))
+        .chain(report.warnings.iter().map(|it| (it, Severity::Warning)));
+    for (issue, severity) in issues {
+        diagnostics.push(Diagnostic {
+            file: issue.span.file.clone(),
+            line: Some(issue.span.line),
+            severity,
+            message: issue.message.clone(),
+        });
+    }
+
+    for command in &report.commands {
+        let mut file = Diagnostics {
+            file: command.span.file.clone(),
+            ..Diagnostics::default()
+        };
+        validator.command(&mut file, command.span.line, &command.kind);
+        diagnostics.append(&mut file.list);
+    }
+
+    diagnostics.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    diagnostics
+}
+
+/// Diagnostics for the config and the lists in use: the custom list, its
+/// includes and drop-ins and the lists of enabled modules.
+pub fn validate(paths: &Paths) -> Vec<Diagnostic> {
+    let partitions = Config::load_or_default(&paths.config_file).partitions;
+    let mut diagnostics = validate_config(&paths.config_file);
+    diagnostics.extend(validate_list(
+        &load_report(paths, Facts::detect()),
+        &partitions,
+    ));
+    diagnostics
+}
+
+/// Prints every diagnostic and fails if any of them is an error.
+pub fn handle_validate(paths: &Paths, output: Output) -> Result<()> {
+    let diagnostics = validate(paths);
+    output.list(&diagnostics)?;
+
+    let errors = diagnostics
+        .iter()
+        .filter(|it| it.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(Error::Validation { errors });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "../tests/unit/validate.rs"]
+mod tests;
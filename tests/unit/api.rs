@@ -0,0 +1,149 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{fs, path::PathBuf};
+
+use super::*;
+
+fn state() -> (tempfile::TempDir, Paths) {
+    let temp = tempfile::tempdir().unwrap();
+    let paths = Paths {
+        module_dir: temp.path().join("modules"),
+        config_file: temp.path().join("config.toml"),
+        state_dir: temp.path().to_path_buf(),
+        work_root: PathBuf::from("/nonexistent"),
+    };
+    (temp, paths)
+}
+
+fn call_json(request: &str, paths: &Paths) -> Value {
+    serde_json::to_value(respond(request, paths)).unwrap()
+}
+
+#[test]
+fn capabilities_list_methods_and_schemas() {
+    let (_temp, paths) = state();
+    let response = call_json(r#"{"method": "capabilities", "version": 1}"#, &paths);
+
+    assert_eq!(response["ok"], true);
+    assert_eq!(response["version"], API_VERSION);
+    assert!(response.get("error").is_none());
+    let result = &response["result"];
+    assert_eq!(result["api_version"], API_VERSION);
+    assert_eq!(result["schemas"]["config"], CONFIG_SCHEMA);
+    assert_eq!(result["schemas"]["module"], MODULE_SCHEMA);
+    assert!(
+        result["methods"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("config.set"))
+    );
+}
+
+#[test]
+fn request_errors_have_codes() {
+    let (_temp, paths) = state();
+    let code = |request: &str| call_json(request, &paths)["error"]["code"].clone();
+
+    assert_eq!(code("not json"), "invalid_request");
+    assert_eq!(code(r#"{"params": {}}"#), "invalid_request");
+    assert_eq!(
+        code(r#"{"method": "version", "extra": 1}"#),
+        "invalid_request"
+    );
+    assert_eq!(code(r#"{"method": "mount.all"}"#), "unknown_method");
+    assert_eq!(
+        code(r#"{"method": "module_rules.get", "params": {}}"#),
+        "invalid_params"
+    );
+    assert_eq!(
+        code(r#"{"method": "module_rules.get", "params": {"id": "../x"}}"#),
+        "invalid_params"
+    );
+
+    let response = call_json(r#"{"method": "version", "version": 2}"#, &paths);
+    assert_eq!(response["ok"], false);
+    assert!(response.get("result").is_none());
+    assert_eq!(response["error"]["code"], "unsupported_version");
+    assert_eq!(response["error"]["details"]["supported"], json!([1]));
+}
+
+#[test]
+fn config_round_trips() {
+    let (_temp, paths) = state();
+
+    let response = call_json(
+        r#"{"method": "config.set", "params": {"partitions": ["mi_ext"], "umount": false,
+            "ignore_list": ["/system/app/Foo"]}}"#,
+        &paths,
+    );
+    assert_eq!(response["ok"], true, "{response}");
+
+    let config = &call_json(r#"{"method": "config.get"}"#, &paths)["result"];
+    assert_eq!(config["partitions"], json!(["mi_ext"]));
+    assert_eq!(config["umount"], false);
+    assert_eq!(config["ignore_list"], json!(["/system/app/Foo"]));
+    assert!(config.get("disable_umount").is_none());
+    assert!(config.get("ignoreList").is_none());
+
+    // the legacy spellings only belong to show-config and save-config
+    let response = call_json(
+        r#"{"method": "config.set", "params": {"disable_umount": true}}"#,
+        &paths,
+    );
+    assert_eq!(response["error"]["code"], "invalid_params");
+    assert!(
+        fs::read_to_string(paths.custom_list())
+            .unwrap()
+            .contains("/system/app/Foo")
+    );
+
+    assert_eq!(
+        call_json(r#"{"method": "config.reset"}"#, &paths)["ok"],
+        true
+    );
+    let config = &call_json(r#"{"method": "config.get"}"#, &paths)["result"];
+    assert_eq!(config["partitions"], json!([]));
+    assert!(
+        !fs::read_to_string(paths.custom_list())
+            .unwrap()
+            .contains("/system/app/Foo")
+    );
+}
+
+#[test]
+fn module_rules_round_trip() {
+    let (temp, paths) = state();
+
+    let response = call_json(
+        r#"{"method": "module_rules.set", "params": {"id": "example",
+            "rules": {"default_mode": "ignore"}}}"#,
+        &paths,
+    );
+    assert_eq!(response["ok"], true, "{response}");
+    assert!(temp.path().join("rules/example.json").exists());
+
+    let rules = &call_json(
+        r#"{"method": "module_rules.get", "params": {"id": "example"}}"#,
+        &paths,
+    )["result"];
+    assert_eq!(rules["default_mode"], "ignore");
+}
+
+#[test]
+fn failures_carry_their_causes() {
+    let (temp, paths) = state();
+    fs::write(temp.path().join("last_run.json"), "{").unwrap();
+
+    let response = call_json(r#"{"method": "report"}"#, &paths);
+    assert_eq!(response["error"]["code"], "failed");
+
+    fs::remove_file(temp.path().join("last_run.json")).unwrap();
+    let response = call_json(r#"{"method": "report"}"#, &paths);
+    assert_eq!(response["error"]["code"], "failed");
+    assert_eq!(
+        response["error"]["message"],
+        "no boot report, has magic mount run yet?"
+    );
+    assert!(response["error"]["details"]["causes"].is_array());
+}
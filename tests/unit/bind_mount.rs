@@ -107,3 +107,180 @@ fn rejects_unsupported_mirror_entries() {
     let error = validate_mirror_entry(&entry).unwrap_err();
     assert!(error.to_string().contains("unsupported file type"));
 }
+
+#[test]
+fn binds_existing_target_read_only() {
+    use crate::mount_backend::{MountOp, RecordingBackend};
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    fs::write(&source, b"source").unwrap();
+    fs::write(&target, b"target").unwrap();
+    let backend = RecordingBackend::default();
+    let mounts = mount_list::MountList::new(tempdir.path().join("umount.list")).unwrap();
+
+    let mounted = mount_target(
+        &source,
+        &target,
+        &BindOptions::default(),
+        tempdir.path(),
+        &mounts,
+        &backend,
+    )
+    .unwrap();
+    assert_eq!(mounted, fs::canonicalize(&target).unwrap());
+    assert_eq!(
+        backend.ops.take(),
+        vec![
+            MountOp::Bind {
+                source,
+                target: mounted.clone(),
+            },
+            MountOp::Remount {
+                target: mounted,
+                flags: MountFlags::BIND | MountFlags::RDONLY,
+            },
+        ]
+    );
+}
+
+#[test]
+fn applies_bind_options() {
+    use crate::{
+        mount_backend::{MountOp, RecordingBackend},
+        parser::Propagation,
+    };
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    fs::create_dir(&source).unwrap();
+    fs::create_dir(&target).unwrap();
+    let backend = RecordingBackend::default();
+    let mounts = mount_list::MountList::new(tempdir.path().join("umount.list")).unwrap();
+    let options = BindOptions {
+        rw: true,
+        recursive: true,
+        nosuid: true,
+        propagation: Some(Propagation::Private),
+        ..BindOptions::default()
+    };
+
+    let mounted = mount_target(
+        &source,
+        &target,
+        &options,
+        tempdir.path(),
+        &mounts,
+        &backend,
+    )
+    .unwrap();
+    assert_eq!(
+        backend.ops.take(),
+        vec![
+            MountOp::BindRecursive {
+                source,
+                target: mounted.clone(),
+            },
+            MountOp::Remount {
+                target: mounted.clone(),
+                flags: MountFlags::BIND | MountFlags::NOSUID,
+            },
+            MountOp::Change {
+                target: mounted,
+                flags: MountPropagationFlags::PRIVATE | MountPropagationFlags::REC,
+            },
+        ]
+    );
+}
+
+#[test]
+fn labels_source_before_binding() {
+    use crate::mount_backend::{MountOp, RecordingBackend};
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    fs::create_dir(&source).unwrap();
+    fs::write(source.join("config"), b"").unwrap();
+    fs::create_dir(&target).unwrap();
+    let backend = RecordingBackend::default();
+    let mounts = mount_list::MountList::new(tempdir.path().join("umount.list")).unwrap();
+    let options = BindOptions {
+        context: Some("u:object_r:system_file:s0".to_string()),
+        ..BindOptions::default()
+    };
+
+    let mounted = mount_target(
+        &source,
+        &target,
+        &options,
+        tempdir.path(),
+        &mounts,
+        &backend,
+    )
+    .unwrap();
+    let label = |path: PathBuf| MountOp::SetXattr {
+        path,
+        name: crate::defs::SELINUX_XATTR.to_string(),
+        value: b"u:object_r:system_file:s0".to_vec(),
+    };
+    assert_eq!(
+        backend.ops.take()[..3],
+        [
+            label(source.clone()),
+            label(source.join("config")),
+            MountOp::Bind {
+                source,
+                target: mounted,
+            },
+        ]
+    );
+}
+
+#[test]
+fn stages_missing_target_under_work_root() {
+    use crate::mount_backend::{MountOp, RecordingBackend};
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let root = tempdir.path().join("root");
+    let work_root = tempdir.path().join("work");
+    fs::write(&source, b"source").unwrap();
+    fs::create_dir(&root).unwrap();
+    fs::create_dir(&work_root).unwrap();
+    let backend = RecordingBackend::default();
+    let mounts = mount_list::MountList::new(tempdir.path().join("umount.list")).unwrap();
+
+    let mounted = mount_target(
+        &source,
+        &root.join("new"),
+        &BindOptions::default(),
+        &work_root,
+        &mounts,
+        &backend,
+    )
+    .unwrap();
+    assert_eq!(mounted, root);
+
+    let ops = backend.ops.take();
+    let MountOp::Bind { source: staged, .. } = &ops[0] else {
+        panic!("unexpected first op: {:?}", ops[0]);
+    };
+    assert_eq!(staged.parent(), Some(work_root.as_path()));
+    assert!(
+        staged
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with(defs::BIND_WORK_DIR_PREFIX)
+    );
+    assert_eq!(
+        ops.last(),
+        Some(&MountOp::Move {
+            source: staged.clone(),
+            target: root,
+        })
+    );
+}
@@ -0,0 +1,164 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::{fs, path::Path};
+
+use clap::{CommandFactory, error::ErrorKind};
+
+use super::*;
+
+fn parse(args: &[&str]) -> std::result::Result<Cli, clap::Error> {
+    Cli::try_parse_from(std::iter::once("magic_mount").chain(args.iter().copied()))
+}
+
+#[test]
+fn definition_is_consistent() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn no_command_is_the_boot_mount() {
+    let cli = parse(&[]).unwrap();
+
+    assert!(cli.command.is_none());
+    assert!(!cli.dry_run);
+    assert_eq!(cli.output(), Output::Text);
+}
+
+#[test]
+fn save_config_takes_one_payload_source() {
+    let cli = parse(&["save-config", "--payload", "7b7d"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Commands::SaveConfig { payload: PayloadArgs { payload: Some(payload), .. } })
+            if payload == "7b7d"
+    ));
+
+    let cli = parse(&["save-config", "--payload-file", "/tmp/config.json"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Commands::SaveConfig { payload: PayloadArgs { payload_file: Some(path), .. } })
+            if path == Path::new("/tmp/config.json")
+    ));
+
+    assert!(matches!(
+        parse(&["save-config", "--stdin"]).unwrap().command,
+        Some(Commands::SaveConfig {
+            payload: PayloadArgs { stdin: true, .. }
+        })
+    ));
+
+    assert_eq!(
+        parse(&["save-config"]).unwrap_err().kind(),
+        ErrorKind::MissingRequiredArgument
+    );
+    assert_eq!(
+        parse(&["save-config", "--payload", "7b7d", "--stdin"])
+            .unwrap_err()
+            .kind(),
+        ErrorKind::ArgumentConflict
+    );
+    assert_eq!(
+        parse(&["save-config", "--wrong-flag"]).unwrap_err().kind(),
+        ErrorKind::UnknownArgument
+    );
+}
+
+#[test]
+fn hex_payloads() {
+    let payload = |hex: &str| PayloadArgs {
+        payload: Some(hex.to_string()),
+        ..PayloadArgs::default()
+    };
+
+    assert_eq!(payload("7b7d").read().unwrap(), "{}");
+    assert_eq!(payload("7B7D").read().unwrap(), "{}");
+    assert_eq!(payload("").read().unwrap(), "");
+    assert!(payload("7b7").read().is_err());
+    assert!(payload("zz").read().is_err());
+    assert!(payload("ff").read().is_err());
+    assert!(PayloadArgs::default().read().is_err());
+}
+
+#[test]
+fn payload_files_are_limited() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("payload.json");
+    let payload = PayloadArgs {
+        payload_file: Some(path.clone()),
+        ..PayloadArgs::default()
+    };
+    assert!(payload.read().is_err());
+
+    fs::write(&path, r#"{"umount": true}"#).unwrap();
+    assert_eq!(payload.read().unwrap(), r#"{"umount": true}"#);
+
+    fs::write(&path, vec![b' '; MAX_PAYLOAD_SIZE + 1]).unwrap();
+    assert!(matches!(
+        payload.read(),
+        Err(Error::PayloadTooLarge {
+            limit: MAX_PAYLOAD_SIZE,
+            ..
+        })
+    ));
+    assert_eq!(read_limited(&[b' '; 16][..], "test").unwrap().len(), 16);
+}
+
+#[test]
+fn module_rules_actions() {
+    let cli = parse(&["module-rules", "set", "example", "--payload", "7b7d"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Commands::ModuleRules {
+            action: RulesAction::Set { module_id, payload: PayloadArgs { payload: Some(payload), .. } }
+        }) if module_id == "example" && payload == "7b7d"
+    ));
+
+    assert_eq!(
+        parse(&["module-rules", "get"]).unwrap_err().kind(),
+        ErrorKind::MissingRequiredArgument
+    );
+    assert_eq!(
+        parse(&["module-rules", "drop", "example"])
+            .unwrap_err()
+            .kind(),
+        ErrorKind::InvalidSubcommand
+    );
+}
+
+#[test]
+fn global_options_go_anywhere() {
+    let cli = parse(&[
+        "--state-dir",
+        "/state",
+        "plan",
+        "--json",
+        "--work-root=/work",
+    ])
+    .unwrap();
+
+    assert!(matches!(cli.command, Some(Commands::Plan)));
+    assert_eq!(cli.output(), Output::Json);
+    assert_eq!(cli.paths.state_dir.as_deref(), Some(Path::new("/state")));
+    assert_eq!(cli.paths.work_root.as_deref(), Some(Path::new("/work")));
+
+    assert_eq!(parse(&["validate", "-q"]).unwrap().output(), Output::Quiet);
+    assert_eq!(
+        parse(&["validate", "--json", "--quiet"])
+            .unwrap_err()
+            .kind(),
+        ErrorKind::ArgumentConflict
+    );
+}
+
+#[test]
+fn rejects_unknown_commands() {
+    assert_eq!(
+        parse(&["mount-everything"]).unwrap_err().kind(),
+        ErrorKind::InvalidSubcommand
+    );
+    assert_eq!(
+        parse(&["--help"]).unwrap_err().kind(),
+        ErrorKind::DisplayHelp
+    );
+}
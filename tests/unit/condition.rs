@@ -0,0 +1,134 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use super::*;
+use crate::parser::{Statement, Variables, parse_line};
+
+fn fixture() -> (tempfile::TempDir, Facts) {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path();
+    fs::create_dir_all(root.join("system")).unwrap();
+    fs::create_dir_all(root.join("vendor")).unwrap();
+    fs::create_dir_all(root.join("proc/sys/kernel")).unwrap();
+    fs::write(
+        root.join("system/build.prop"),
+        "ro.product.system.device=raven\nro.build.version.sdk=34\n",
+    )
+    .unwrap();
+    fs::write(
+        root.join("vendor/build.prop"),
+        "ro.build.version.sdk=33\nro.vendor.build.fingerprint=google/raven/raven:14\n",
+    )
+    .unwrap();
+    fs::create_dir_all(root.join("product/etc")).unwrap();
+    fs::write(
+        root.join("product/etc/build.prop"),
+        "ro.product.product.name=raven_beta\nro.build.version.sdk=32\n",
+    )
+    .unwrap();
+    fs::write(
+        root.join("proc/sys/kernel/osrelease"),
+        "5.10.198-android13\n",
+    )
+    .unwrap();
+
+    let facts = Facts::load(root, Some("ksu"));
+    (temp, facts)
+}
+
+fn condition(line: &str) -> Condition {
+    match parse_line(line, &Variables::default()).map(|it| it.statement) {
+        Ok(Statement::If(condition)) => condition,
+        _ => panic!("{line} is not an if"),
+    }
+}
+
+#[test]
+fn props_come_from_build_prop_files() {
+    let (_temp, facts) = fixture();
+
+    assert!(condition("if prop ro.product.system.device raven").eval(&facts));
+    assert!(condition("if prop ro.build.version.sdk 34").eval(&facts));
+    assert!(condition("if prop ro.vendor.build.fingerprint google/*/raven:*").eval(&facts));
+    assert!(condition("if prop ro.product.system.device").eval(&facts));
+    assert!(condition("if prop ro.product.product.name raven_*").eval(&facts));
+    assert!(!condition("if prop ro.product.system.device oriole").eval(&facts));
+    assert!(!condition("if prop ro.missing").eval(&facts));
+}
+
+#[test]
+fn value_globs_cross_slashes() {
+    assert!(glob_match(
+        "*/release-keys",
+        "google/raven/raven:14/release-keys"
+    ));
+    assert!(glob_match(
+        "google/*:14/*",
+        "google/raven/raven:14/release-keys"
+    ));
+    assert!(glob_match("5.10.?-*", "5.10.1-android12/9"));
+    assert!(!glob_match(
+        "*/user-keys",
+        "google/raven/raven:14/release-keys"
+    ));
+}
+
+#[test]
+fn kernel_exists_root_and_not() {
+    let (temp, facts) = fixture();
+
+    assert!(condition("if kernel 5.10.*").eval(&facts));
+    assert!(!condition("if kernel 6.*").eval(&facts));
+    assert!(condition("if exists /vendor/build.prop").eval(&facts));
+    assert!(!condition("if exists /odm").eval(&facts));
+    assert!(condition("if root ksu").eval(&facts));
+    assert!(condition("if not root magisk").eval(&facts));
+    assert!(condition(&format!("if ! exists {}/odm", temp.path().display())).eval(&facts));
+}
+
+#[test]
+fn rejects_bad_conditions() {
+    let error = |line: &str| match parse_line(line, &Variables::default()).map(|it| it.statement) {
+        Err((column, error)) => (column, error),
+        Ok(_) => panic!("{line} parsed"),
+    };
+
+    assert_eq!(
+        error("if"),
+        (
+            2,
+            ParseError::MissingArgument {
+                command: "if".to_string(),
+                argument: "condition",
+            }
+        )
+    );
+    assert_eq!(
+        error("if uptime 5"),
+        (
+            3,
+            ParseError::UnknownCondition {
+                name: "uptime".to_string()
+            }
+        )
+    );
+    assert_eq!(
+        error("if root su"),
+        (
+            8,
+            ParseError::UnknownRootManager {
+                name: "su".to_string()
+            }
+        )
+    );
+    assert_eq!(
+        error("if kernel"),
+        (
+            9,
+            ParseError::MissingArgument {
+                command: "kernel".to_string(),
+                argument: "pattern",
+            }
+        )
+    );
+}
@@ -3,43 +3,6 @@
 
 use super::*;
 
-#[test]
-fn test_decode_hex_valid_cases() {
-    let input_lowercase = "74657374";
-    let input_uppercase = "54455354";
-    let input_extremes = "00ff00ff";
-
-    assert_eq!(decode(input_lowercase).unwrap(), b"test".to_vec());
-    assert_eq!(decode(input_uppercase).unwrap(), b"TEST".to_vec());
-    assert_eq!(decode("").unwrap(), Vec::<u8>::new());
-    assert_eq!(
-        decode(input_extremes).unwrap(),
-        vec![0x00, 0xFF, 0x00, 0xFF]
-    );
-}
-
-#[test]
-fn test_decode_hex_invalid_length_error() {
-    assert!(decode("a").is_err());
-    assert!(decode("12345").is_err());
-}
-
-#[test]
-fn test_parse_payload_arg_success() {
-    let args = vec![
-        "magic_mount".to_string(),
-        "--payload".to_string(),
-        "7b7d".to_string(),
-    ];
-    assert_eq!(parse_payload_arg(&args).unwrap(), "7b7d");
-}
-
-#[test]
-fn test_parse_payload_arg_missing() {
-    let args = vec!["magic_mount".to_string(), "--wrong-flag".to_string()];
-    assert!(parse_payload_arg(&args).is_err());
-}
-
 #[test]
 fn test_format_custom_path() {
     assert_eq!(
@@ -59,7 +22,7 @@ fn test_apply_api_payload() {
         mountsource: Some("APatch".to_string()),
         partitions: Some(vec!["system".to_string(), "product".to_string()]),
         umount: Some(true),
-        disable_umount: None,
+        priority: None,
         ignore_list: None,
         custom_mounts: None,
     };
@@ -76,20 +39,34 @@ fn test_apply_api_payload_disable_umount() {
         mountsource: "KSU".to_string(),
         partitions: vec![],
         umount: true,
+        priority: vec![],
     };
-    let payload = ApiConfigPayload {
+    let payload = LegacyApiConfigPayload {
         mountsource: None,
         partitions: None,
         umount: None,
         disable_umount: Some(true),
+        priority: None,
         ignore_list: None,
         custom_mounts: None,
     };
 
-    config.apply_api_payload(payload);
+    config.apply_api_payload(payload.into());
     assert!(!config.umount);
 }
 
+#[test]
+fn test_legacy_payload_accepts_both_spellings() {
+    let payload: ApiConfigPayload = serde_json::from_str::<LegacyApiConfigPayload>(
+        r#"{"ignore_list": ["/a"], "customMounts": [], "disable_umount": false}"#,
+    )
+    .unwrap()
+    .into();
+    assert_eq!(payload.ignore_list, Some(vec!["/a".to_string()]));
+    assert_eq!(payload.custom_mounts.map(|it| it.len()), Some(0));
+    assert_eq!(payload.umount, Some(true));
+}
+
 #[test]
 fn test_config_save_and_load_flow() {
     let tmp_dir = tempfile::tempdir().unwrap();
@@ -97,9 +74,11 @@ fn test_config_save_and_load_flow() {
     let mut config = Config::default();
     config.partitions = vec!["vendor".to_string()];
     config.umount = true;
+    config.priority = vec!["first".to_string()];
     assert!(config.save(&config_file_path).is_ok());
 
     let loaded_config = Config::load(&config_file_path).unwrap();
+    assert_eq!(loaded_config.priority, vec!["first"]);
     assert_eq!(loaded_config.mountsource, "KSU");
     assert_eq!(loaded_config.partitions, vec!["vendor"]);
     assert!(loaded_config.umount);
@@ -137,11 +116,121 @@ fn test_into_api_struct_mapping() {
         mountsource: "KSU".to_string(),
         partitions: vec!["system".to_string()],
         umount: false,
+        priority: vec!["b".to_string(), "a".to_string()],
     };
-    let api_config = config.into_api(vec!["/data/local/tmp".to_string()], vec![]);
+    let api_config = config.into_api(
+        Path::new("/data/adb/modules/"),
+        vec!["/data/local/tmp".to_string()],
+        vec![],
+    );
+    assert_eq!(api_config.moduledir, "/data/adb/modules");
     assert_eq!(api_config.mountsource, "KSU");
     assert_eq!(api_config.partitions, vec!["system"]);
     assert!(!api_config.umount);
-    assert!(api_config.disable_umount);
+    assert_eq!(api_config.priority, vec!["b", "a"]);
     assert_eq!(api_config.ignore_list[0], "/data/local/tmp");
+
+    let legacy = LegacyApiConfig::from(api_config);
+    assert!(legacy.disable_umount);
+    assert_eq!(legacy.ignore_list[0], "/data/local/tmp");
+}
+
+#[test]
+fn test_write_custom_list_round_trips_dashed_sources() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let list_file_path = tmp_dir.path().join("custom.list");
+    let custom_mounts = vec![ApiCustomMount {
+        source: "--rw".to_string(),
+        target: "/system/etc/hosts".to_string(),
+    }];
+
+    Config::write_custom_list(&list_file_path, &[], &custom_mounts).unwrap();
+    assert_eq!(
+        fs::read_to_string(&list_file_path).unwrap(),
+        "bind -- --rw /system/etc/hosts\n"
+    );
+    let (_, read_back) = Config::read_custom_lists(&list_file_path);
+    assert_eq!(read_back.len(), 1);
+    assert_eq!(read_back[0].source, "--rw");
+}
+
+#[test]
+fn test_write_custom_list_keeps_unmanaged_lines() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let list_file_path = tmp_dir.path().join("custom.list");
+    fs::write(
+        &list_file_path,
+        "# keep me\nignore /old\nignore-re '\\.odex$'\nfile /data/extra.rules\nbind /a /b\n\
+         bind --rw /c /d\nif root ksu\nignore /ksu\nendif\nignore ${MODULES}/x\n",
+    )
+    .unwrap();
+
+    Config::write_custom_list(&list_file_path, &["/new".to_string()], &[]).unwrap();
+    assert_eq!(
+        fs::read_to_string(&list_file_path).unwrap(),
+        "# keep me\nignore-re '\\.odex$'\nfile /data/extra.rules\nbind --rw /c /d\nif root ksu\n\
+         ignore /ksu\nendif\nignore ${MODULES}/x\nignore /new\n"
+    );
+}
+
+#[test]
+fn test_save_keeps_comments_and_unknown_keys() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let path = tmp_dir.path().join("config.toml");
+    fs::write(
+        &path,
+        "# managed by hand\nmountsource = \"KSU\"\numount = false # off for now\n\
+         partitions = [\n    \"mi_ext\",\n]\nfuture_key = \"kept\"\n\n[future]\nvalue = 1\n",
+    )
+    .unwrap();
+
+    let mut config = Config::load(&path).unwrap();
+    config.umount = true;
+    config.priority = vec!["first".to_string()];
+    config.save(&path).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "# managed by hand\nmountsource = \"KSU\"\numount = true # off for now\n\
+         partitions = [\n    \"mi_ext\",\n]\nfuture_key = \"kept\"\npriority = [\"first\"]\n\n\
+         [future]\nvalue = 1\n"
+    );
+}
+
+#[test]
+fn test_load_falls_back_per_field() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let path = tmp_dir.path().join("config.toml");
+    fs::write(
+        &path,
+        "mountsource = 1\npartitions = [\"mi_ext\"]\numount = true\n",
+    )
+    .unwrap();
+
+    let table: toml::Table = toml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    let (config, errors) = Config::from_table(&table);
+    assert_eq!(config.mountsource, "KSU");
+    assert_eq!(config.partitions, vec!["mi_ext"]);
+    assert!(config.umount);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].key, "mountsource");
+
+    let config = Config::load(&path).unwrap();
+    assert_eq!(config.partitions, vec!["mi_ext"]);
+
+    let (config, errors) = Config::load_for_boot(&path);
+    assert!(config.umount);
+    assert_eq!(errors.len(), 1);
+
+    fs::write(&path, "umount = \n").unwrap();
+    assert!(Config::load(&path).is_err());
+    assert!(Config::default().save(&path).is_err());
+    let (config, errors) = Config::load_for_boot(&path);
+    assert_eq!(config.mountsource, "KSU");
+    assert!(!config.umount);
+    assert_eq!(errors.len(), 1);
+
+    let (config, errors) = Config::load_for_boot(tmp_dir.path().join("missing.toml"));
+    assert_eq!(config.mountsource, "KSU");
+    assert!(errors.is_empty());
 }
@@ -0,0 +1,124 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use super::*;
+use crate::parser::BindOptions;
+
+fn glob_matches(glob: &str, path: &str) -> bool {
+    Regex::new(&glob_to_regex(glob)).unwrap().is_match(path)
+}
+
+#[test]
+fn star_and_question_mark_stay_within_a_component() {
+    assert!(glob_matches("/system/app/*.apk", "/system/app/Foo.apk"));
+    assert!(!glob_matches(
+        "/system/app/*.apk",
+        "/system/app/Foo/Foo.apk"
+    ));
+    assert!(glob_matches("/system/lib?", "/system/lib6"));
+    assert!(!glob_matches("/system/lib?", "/system/lib/"));
+}
+
+#[test]
+fn double_star_crosses_directories() {
+    let glob = "/data/adb/modules/*/system/app/**/oat";
+    assert!(glob_matches(glob, "/data/adb/modules/a/system/app/oat"));
+    assert!(glob_matches(glob, "/data/adb/modules/a/system/app/X/oat"));
+    assert!(glob_matches(glob, "/data/adb/modules/a/system/app/X/Y/oat"));
+    assert!(!glob_matches(
+        glob,
+        "/data/adb/modules/a/b/system/app/X/oat"
+    ));
+    assert!(!glob_matches(
+        glob,
+        "/data/adb/modules/a/system/app/X/oat/arm64"
+    ));
+    assert!(glob_matches("/system/**", "/system/etc/hosts"));
+}
+
+#[test]
+fn character_classes_and_literals() {
+    assert!(glob_matches("/lib[0-9]", "/lib6"));
+    assert!(!glob_matches("/lib[!0-9]", "/lib6"));
+    assert!(glob_matches("/lib[!0-9]", "/libx"));
+    assert!(glob_matches("/a[]]b", "/a]b"));
+    assert!(glob_matches("/a[b", "/a[b"));
+    assert!(glob_matches("/a.b+(c)", "/a.b+(c)"));
+    assert!(!glob_matches("/a.b", "/axb"));
+}
+
+#[test]
+fn matcher_combines_literals_globs_and_regexes() {
+    let matcher = IgnoreMatcher::new(&[
+        MountType::Ignore {
+            source: "/m/a/system/etc/hosts".to_string(),
+        },
+        MountType::Ignore {
+            source: "/m/*/system/app/**/oat".to_string(),
+        },
+        MountType::IgnoreRe {
+            pattern: r"\.odex$".to_string(),
+        },
+        MountType::IgnoreRe {
+            pattern: "(unclosed".to_string(),
+        },
+        MountType::Mount {
+            source: "/x".to_string(),
+            target: "/y".to_string(),
+            options: BindOptions::default(),
+        },
+    ]);
+
+    assert!(matcher.is_match("/m/a/system/etc/hosts"));
+    assert!(!matcher.is_match("/m/b/system/etc/hosts"));
+    assert!(matcher.is_match("/m/b/system/app/Foo/oat"));
+    assert!(matcher.is_match("/m/b/system/framework/boot.odex"));
+    assert!(!matcher.is_match("/x"));
+    assert!(!IgnoreMatcher::default().is_match("/m/a/system/etc/hosts"));
+}
+
+#[test]
+fn target_matcher_maps_partitions_under_system() {
+    let matcher = IgnoreMatcher::for_targets(&[
+        MountType::IgnoreTarget {
+            target: "/system/etc/hosts".to_string(),
+        },
+        MountType::IgnoreTarget {
+            target: "/vendor/etc/*.xml".to_string(),
+        },
+        MountType::Ignore {
+            source: "/system/etc/fonts.xml".to_string(),
+        },
+    ]);
+
+    assert!(matcher.is_match("/system/etc/hosts"));
+    assert!(matcher.is_match("/system/vendor/etc/audio.xml"));
+    assert!(!matcher.is_match("/system/etc/audio.xml"));
+    assert!(!matcher.is_match("/system/etc/fonts.xml"));
+    assert!(
+        !IgnoreMatcher::new(&[MountType::IgnoreTarget {
+            target: "/system/etc/hosts".to_string(),
+        }])
+        .is_match("/system/etc/hosts")
+    );
+}
+
+#[test]
+fn glob_paths_expand_in_lexical_order() {
+    let temp = tempfile::tempdir().unwrap();
+    for path in ["x/b.list", "x/a.list", "y/c.list", "y/c.txt"] {
+        let path = temp.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    assert_eq!(
+        glob_paths(&temp.path().join("*/*.list")),
+        ["x/a.list", "x/b.list", "y/c.list"].map(|it| temp.path().join(it))
+    );
+    assert_eq!(
+        glob_paths(&temp.path().join("y/c.txt")),
+        [temp.path().join("y/c.txt")]
+    );
+    assert!(glob_paths(&temp.path().join("z/*.list")).is_empty());
+}
@@ -0,0 +1,361 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::os::unix::fs::MetadataExt;
+
+use super::*;
+use crate::{
+    defs,
+    mount_backend::{MountOp, RecordingBackend},
+};
+
+struct Fixture {
+    temp: tempfile::TempDir,
+    root: PathBuf,
+    work: PathBuf,
+    module: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("root");
+        let work = temp.path().join("work");
+        let module = temp.path().join("module/system/etc");
+        fs::create_dir_all(root.join("system/etc")).unwrap();
+        fs::create_dir_all(&work).unwrap();
+        fs::create_dir_all(&module).unwrap();
+        fs::write(root.join("system/etc/hosts"), "").unwrap();
+        Self {
+            temp,
+            root,
+            work,
+            module,
+        }
+    }
+
+    fn mount(&self, etc: Node) -> Vec<MountOp> {
+        let mut system = Node::new_for_test("system", NodeFileType::Directory, None);
+        system.children.insert(etc.name.clone(), etc);
+
+        let backend = RecordingBackend::default();
+        let mounts = mount_list::MountList::new(self.temp.path().join("umount.list")).unwrap();
+        MagicMount::new(
+            &system, &self.root, &self.work, false, false, &mounts, &backend,
+        )
+        .do_mount()
+        .unwrap();
+
+        backend.ops.take()
+    }
+}
+
+#[test]
+fn binds_replaced_file_in_place() {
+    let fixture = Fixture::new();
+    fs::write(fixture.module.join("hosts"), "").unwrap();
+
+    let mut etc = Node::new_for_test("etc", NodeFileType::Directory, Some(fixture.module.clone()));
+    etc.children.insert(
+        "hosts".to_string(),
+        Node::new_for_test(
+            "hosts",
+            NodeFileType::RegularFile,
+            Some(fixture.module.join("hosts")),
+        ),
+    );
+
+    let target = fixture.root.join("system/etc/hosts");
+    assert_eq!(
+        fixture.mount(etc),
+        vec![
+            MountOp::Bind {
+                source: fixture.module.join("hosts"),
+                target: target.clone(),
+            },
+            MountOp::Remount {
+                target,
+                flags: MountFlags::RDONLY | MountFlags::BIND,
+            },
+        ]
+    );
+}
+
+#[test]
+fn labels_module_file_from_rules() {
+    let fixture = Fixture::new();
+    fs::write(fixture.module.join("hosts"), "").unwrap();
+
+    let mut hosts = Node::new_for_test(
+        "hosts",
+        NodeFileType::RegularFile,
+        Some(fixture.module.join("hosts")),
+    );
+    hosts.context = Some("u:object_r:vendor_configs_file:s0".to_string());
+    let mut etc = Node::new_for_test("etc", NodeFileType::Directory, Some(fixture.module.clone()));
+    etc.children.insert("hosts".to_string(), hosts);
+
+    assert_eq!(
+        fixture.mount(etc)[..2],
+        [
+            MountOp::SetXattr {
+                path: fixture.module.join("hosts"),
+                name: defs::SELINUX_XATTR.to_string(),
+                value: b"u:object_r:vendor_configs_file:s0".to_vec(),
+            },
+            MountOp::Bind {
+                source: fixture.module.join("hosts"),
+                target: fixture.root.join("system/etc/hosts"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn builds_tmpfs_for_new_file() {
+    let fixture = Fixture::new();
+    fs::write(fixture.module.join("new"), "").unwrap();
+
+    let mut etc = Node::new_for_test("etc", NodeFileType::Directory, Some(fixture.module.clone()));
+    etc.children.insert(
+        "new".to_string(),
+        Node::new_for_test(
+            "new",
+            NodeFileType::RegularFile,
+            Some(fixture.module.join("new")),
+        ),
+    );
+
+    let (real, work) = (
+        fixture.root.join("system/etc"),
+        fixture.work.join("system/etc"),
+    );
+    assert_eq!(
+        fixture.mount(etc),
+        vec![
+            MountOp::SetXattr {
+                path: work.clone(),
+                name: defs::SELINUX_XATTR.to_string(),
+                value: b"u:object_r:system_file:s0".to_vec(),
+            },
+            MountOp::Bind {
+                source: work.clone(),
+                target: work.clone(),
+            },
+            MountOp::Bind {
+                source: real.join("hosts"),
+                target: work.join("hosts"),
+            },
+            MountOp::Bind {
+                source: fixture.module.join("new"),
+                target: work.join("new"),
+            },
+            MountOp::Remount {
+                target: work.join("new"),
+                flags: MountFlags::RDONLY | MountFlags::BIND,
+            },
+            MountOp::Remount {
+                target: work.clone(),
+                flags: MountFlags::RDONLY | MountFlags::BIND,
+            },
+            MountOp::Move {
+                source: work,
+                target: real.clone(),
+            },
+            MountOp::Change {
+                target: real,
+                flags: MountPropagationFlags::PRIVATE | MountPropagationFlags::REC,
+            },
+        ]
+    );
+    assert!(fixture.work.join("system/etc/new").is_file());
+}
+
+fn collect_hosts_owners(priority: &[&str]) -> (String, Vec<String>) {
+    crate::parser::COMMAND_LIST.get_or_init(Vec::new);
+    let temp = tempfile::tempdir().unwrap();
+    for (id, prop_priority) in [("alpha", None), ("beta", None), ("gamma", Some(10))] {
+        let module = temp.path().join(id);
+        fs::create_dir_all(module.join("system/etc")).unwrap();
+        fs::write(module.join("system/etc/hosts"), id).unwrap();
+        let prop = prop_priority.map_or_else(
+            || format!("id={id}\n"),
+            |prop_priority| format!("id={id}\npriority={prop_priority}\n"),
+        );
+        fs::write(module.join("module.prop"), prop).unwrap();
+    }
+
+    let config = Config {
+        priority: priority.iter().map(ToString::to_string).collect(),
+        ..Config::default()
+    };
+    let root = collect_module_files(temp.path(), &temp.path().join("rules"), &config)
+        .unwrap()
+        .unwrap();
+    let hosts = &root.children["system"].children["etc"].children["hosts"];
+    (hosts.module_id.clone().unwrap(), hosts.shadowed.clone())
+}
+
+#[test]
+fn orders_modules_by_prop_priority_then_id() {
+    assert_eq!(
+        collect_hosts_owners(&[]),
+        (
+            "gamma".to_string(),
+            vec!["alpha".to_string(), "beta".to_string()]
+        )
+    );
+}
+
+#[test]
+fn config_priority_overrides_prop_priority() {
+    assert_eq!(
+        collect_hosts_owners(&["beta"]),
+        (
+            "beta".to_string(),
+            vec!["gamma".to_string(), "alpha".to_string()]
+        )
+    );
+}
+
+#[test]
+fn records_module_status() {
+    let fixture = Fixture::new();
+    fs::write(fixture.module.join("hosts"), "").unwrap();
+
+    let module_id = Some("status-test".to_string());
+    let mut etc = Node::new_for_test("etc", NodeFileType::Directory, Some(fixture.module.clone()));
+    let mut hosts = Node::new_for_test(
+        "hosts",
+        NodeFileType::RegularFile,
+        Some(fixture.module.join("hosts")),
+    );
+    hosts.module_id.clone_from(&module_id);
+    let mut ignored = Node::new_for_test(
+        "ignored",
+        NodeFileType::RegularFile,
+        Some(fixture.module.join("ignored")),
+    );
+    ignored.module_id.clone_from(&module_id);
+    ignored.skip = true;
+    etc.children.insert("hosts".to_string(), hosts);
+    etc.children.insert("ignored".to_string(), ignored);
+    fixture.mount(etc);
+
+    assert_eq!(
+        status::get("status-test"),
+        Some(status::ModuleStatus {
+            mounted_files: 1,
+            skipped: vec![fixture.root.join("system/etc/ignored")],
+            ..status::ModuleStatus::default()
+        })
+    );
+}
+
+#[test]
+fn hide_mirrors_everything_but_the_hidden_file() {
+    let fixture = Fixture::new();
+    let real = fixture.root.join("system/etc");
+    fs::write(real.join("fstab"), "").unwrap();
+
+    let mut system = Node::new_for_test("system", NodeFileType::Directory, None);
+    assert!(system.hide(&fixture.root.join("system"), Path::new("etc/hosts")));
+    let etc = system.children.remove("etc").unwrap();
+
+    let work = fixture.work.join("system/etc");
+    let ops = fixture.mount(etc);
+    assert!(ops.contains(&MountOp::Bind {
+        source: real.join("fstab"),
+        target: work.join("fstab"),
+    }));
+    assert!(!ops.iter().any(|op| matches!(
+        op,
+        MountOp::Bind { source, .. } if source == &real.join("hosts")
+    )));
+    assert!(ops.contains(&MountOp::Move {
+        source: work,
+        target: real,
+    }));
+}
+
+#[test]
+fn replace_builds_an_empty_tmpfs() {
+    let fixture = Fixture::new();
+    let real = fixture.root.join("system/etc");
+
+    let mut system = Node::new_for_test("system", NodeFileType::Directory, None);
+    assert!(system.replace(&fixture.root.join("system"), Path::new("etc")));
+    let etc = system.children.remove("etc").unwrap();
+
+    let work = fixture.work.join("system/etc");
+    let ops = fixture.mount(etc);
+    assert!(!ops.iter().any(|op| matches!(
+        op,
+        MountOp::Bind { source, .. } if source.starts_with(&real)
+    )));
+    assert!(ops.contains(&MountOp::Move {
+        source: work,
+        target: real,
+    }));
+}
+
+#[test]
+fn creates_custom_symlinks_directories_and_tmpfs() {
+    let fixture = Fixture::new();
+    let real = fixture.root.join("system/etc");
+    let owner = real.metadata().unwrap();
+
+    let mut system = Node::new_for_test("system", NodeFileType::Directory, None);
+    let on_device = fixture.root.join("system");
+    assert!(system.create(
+        &on_device,
+        Path::new("etc/hosts.link"),
+        Synthetic::Symlink {
+            link: PathBuf::from("hosts"),
+        },
+    ));
+    assert!(system.create(
+        &on_device,
+        Path::new("etc/extra"),
+        Synthetic::Directory {
+            mode: 0o750,
+            uid: owner.uid(),
+            gid: owner.gid(),
+            context: Some("u:object_r:vendor_file:s0".to_string()),
+        },
+    ));
+    assert!(system.create(
+        &on_device,
+        Path::new("etc/scratch"),
+        Synthetic::Tmpfs {
+            source: "magic".to_string(),
+            size: Some("16M".to_string()),
+        },
+    ));
+    let etc = system.children.remove("etc").unwrap();
+
+    let work = fixture.work.join("system/etc");
+    let ops = fixture.mount(etc);
+    assert_eq!(
+        fs::read_link(work.join("hosts.link")).unwrap(),
+        Path::new("hosts")
+    );
+    assert_eq!(
+        work.join("extra").metadata().unwrap().mode() & 0o7777,
+        0o750
+    );
+    assert!(ops.contains(&MountOp::SetXattr {
+        path: work.join("extra"),
+        name: defs::SELINUX_XATTR.to_string(),
+        value: b"u:object_r:vendor_file:s0".to_vec(),
+    }));
+    assert!(ops.contains(&MountOp::Tmpfs {
+        source: "magic".to_string(),
+        target: work.join("scratch"),
+        size: Some("16M".to_string()),
+    }));
+    assert!(ops.contains(&MountOp::Move {
+        source: work,
+        target: real,
+    }));
+}
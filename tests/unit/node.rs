@@ -0,0 +1,177 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::fs;
+
+use super::*;
+use crate::scanner::MountMode;
+
+fn collect(modules: &[(&str, &[&str])]) -> Node {
+    collect_with_rules(modules, &ModuleRules::default())
+}
+
+fn collect_with_rules(modules: &[(&str, &[&str])], rules: &ModuleRules) -> Node {
+    crate::parser::COMMAND_LIST.get_or_init(Vec::new);
+    let temp = tempfile::tempdir().unwrap();
+    let mut system = Node::new_root("system");
+
+    for (id, files) in modules {
+        let mod_system = temp.path().join(id).join("system");
+        for file in *files {
+            let path = mod_system.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, id).unwrap();
+        }
+        system.collect_module_files(&mod_system, id, rules).unwrap();
+    }
+
+    system
+}
+
+#[test]
+fn first_module_wins_and_records_shadowed() {
+    let system = collect(&[
+        ("alpha", &["etc/hosts", "etc/alpha.conf"]),
+        ("beta", &["etc/hosts", "etc/beta.conf"]),
+        ("gamma", &["etc/hosts"]),
+    ]);
+
+    let hosts = &system.children["etc"].children["hosts"];
+    assert_eq!(hosts.module_id.as_deref(), Some("alpha"));
+    assert_eq!(
+        system.conflicts(),
+        vec![Conflict {
+            path: PathBuf::from("/system/etc/hosts"),
+            module: "alpha".to_string(),
+            shadowed: vec!["beta".to_string(), "gamma".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn merged_directories_are_not_conflicts() {
+    let system = collect(&[("alpha", &["etc/a"]), ("beta", &["etc/b"])]);
+
+    assert_eq!(system.children["etc"].children.len(), 2);
+    assert!(system.conflicts().is_empty());
+}
+
+#[test]
+fn file_shadowing_directory_is_a_conflict() {
+    let system = collect(&[("alpha", &["etc/hosts/inner"]), ("beta", &["etc/hosts"])]);
+
+    let hosts = &system.children["etc"].children["hosts"];
+    assert_eq!(hosts.file_type, NodeFileType::Directory);
+    assert_eq!(hosts.shadowed, vec!["beta".to_string()]);
+}
+
+#[test]
+fn rules_ignore_paths_and_keep_magic_overrides() {
+    let rules = ModuleRules {
+        default_mode: MountMode::Magic,
+        paths: [
+            ("system/etc".to_string(), MountMode::Ignore),
+            ("system/etc/keep".to_string(), MountMode::Magic),
+        ]
+        .into(),
+        ..ModuleRules::default()
+    };
+    let system = collect_with_rules(&[("alpha", &["etc/hosts", "etc/keep/a", "bin/sh"])], &rules);
+
+    let etc = &system.children["etc"];
+    assert!(!etc.children.contains_key("hosts"));
+    assert!(etc.children["keep"].children.contains_key("a"));
+    assert!(system.children["bin"].children.contains_key("sh"));
+}
+
+#[test]
+fn rules_label_module_files() {
+    let rules = ModuleRules {
+        contexts: [(
+            "system/etc".to_string(),
+            "u:object_r:vendor_configs_file:s0".to_string(),
+        )]
+        .into(),
+        ..ModuleRules::default()
+    };
+    let system = collect_with_rules(&[("alpha", &["etc/hosts", "bin/sh"])], &rules);
+
+    assert_eq!(
+        system.children["etc"].children["hosts"].context.as_deref(),
+        Some("u:object_r:vendor_configs_file:s0")
+    );
+    assert_eq!(system.children["bin"].children["sh"].context, None);
+}
+
+#[test]
+fn hide_and_replace_add_custom_nodes() {
+    let device = tempfile::tempdir().unwrap();
+    let on_device = device.path().join("system");
+    fs::create_dir_all(on_device.join("etc/permissions")).unwrap();
+    fs::create_dir_all(on_device.join("app/Stock")).unwrap();
+    fs::write(on_device.join("etc/hosts"), "").unwrap();
+    fs::write(on_device.join("build.prop"), "").unwrap();
+
+    let mut system = collect(&[("alpha", &["etc/hosts", "app/Stock/extra.apk"])]);
+    assert!(system.hide(&on_device, Path::new("etc/hosts")));
+    assert!(system.replace(&on_device, Path::new("etc/permissions")));
+    assert!(system.replace(&on_device, Path::new("app/Stock")));
+    assert!(!system.hide(&on_device, Path::new("etc/missing")));
+    assert!(!system.replace(&on_device, Path::new("etc/hosts")));
+    assert!(!system.hide(&on_device, Path::new("missing/file")));
+    // the partition root never becomes a tmpfs
+    assert!(!system.hide(&on_device, Path::new("build.prop")));
+    assert!(!system.custom);
+    assert!(!system.children.contains_key("build.prop"));
+
+    let etc = &system.children["etc"];
+    assert_eq!(etc.children["hosts"].file_type, NodeFileType::Whiteout);
+    assert_eq!(etc.children["hosts"].module_id, None);
+
+    assert!(etc.custom);
+
+    // the marker, not a module path, lets the directory become a tmpfs
+    let permissions = &etc.children["permissions"];
+    assert!(permissions.replace);
+    assert!(permissions.custom);
+    assert_eq!(permissions.module_path, None);
+    assert!(permissions.tmpfs_allowed());
+
+    // module files stay in a replaced directory
+    let stock = &system.children["app"].children["Stock"];
+    assert!(stock.replace);
+    assert_eq!(
+        stock.children["extra.apk"].module_id.as_deref(),
+        Some("alpha")
+    );
+    assert!(!system.children.contains_key("missing"));
+}
+
+#[test]
+fn create_replaces_module_files_and_merges_directories() {
+    let device = tempfile::tempdir().unwrap();
+    let on_device = device.path().join("system");
+    fs::create_dir_all(on_device.join("etc")).unwrap();
+
+    let mut system = collect(&[("alpha", &["etc/hosts", "etc/extra/a.conf"])]);
+    let link = Synthetic::Symlink {
+        link: PathBuf::from("/data/hosts"),
+    };
+    let dir = Synthetic::Directory {
+        mode: 0o755,
+        uid: 0,
+        gid: 0,
+        context: None,
+    };
+    assert!(system.create(&on_device, Path::new("etc/hosts"), link.clone()));
+    assert!(system.create(&on_device, Path::new("etc/extra"), dir.clone()));
+    assert!(!system.create(&on_device, Path::new("missing/dir"), dir));
+
+    let etc = &system.children["etc"];
+    assert_eq!(etc.children["hosts"].file_type, NodeFileType::Symlink);
+    assert_eq!(etc.children["hosts"].synthetic, Some(link));
+    assert_eq!(etc.children["hosts"].module_id, None);
+    let extra = &etc.children["extra"];
+    assert!(extra.synthetic.is_some());
+    assert_eq!(extra.children["a.conf"].module_id.as_deref(), Some("alpha"));
+}
@@ -5,39 +5,85 @@ use std::io::Write;
 
 use super::*;
 
+fn statement(input: &str) -> Result<Statement, (usize, ParseError)> {
+    parse_line(input, &Variables::default()).map(|it| it.statement)
+}
+
+fn mount(input: &str) -> Option<MountType> {
+    match statement(input) {
+        Ok(Statement::Mount(mount)) => Some(mount),
+        _ => None,
+    }
+}
+
+fn include(input: &str) -> Option<String> {
+    match statement(input) {
+        Ok(Statement::Include(path)) => Some(path),
+        _ => None,
+    }
+}
+
+/// The whole input as one unquoted, unescaped word.
+fn word(input: &str) -> String {
+    Lexer::new(input, &Variables::default())
+        .word(false)
+        .ok()
+        .flatten()
+        .map(|(_, word)| word)
+        .unwrap_or_default()
+}
+
+fn commands(content: &str) -> Vec<MountType> {
+    let mut report = ParseReport::default();
+    report.parse(Path::new(""), content, &Facts::default(), false);
+    report.commands.into_iter().map(|it| it.kind).collect()
+}
+
+fn file_commands<P>(path: P) -> Vec<MountType>
+where
+    P: AsRef<Path>,
+{
+    ParseReport::from_file(path)
+        .commands
+        .into_iter()
+        .map(|it| it.kind)
+        .collect()
+}
+
 #[test]
 fn parse_path_no_quotes() {
-    assert_eq!(parse_path("/home/user"), "/home/user");
+    assert_eq!(word("/home/user"), "/home/user");
 }
 
 #[test]
 fn parse_path_single_quotes() {
-    assert_eq!(parse_path("'/path/with spaces'"), "/path/with spaces");
+    assert_eq!(word("'/path/with spaces'"), "/path/with spaces");
 }
 
 #[test]
 fn parse_path_double_quotes() {
-    assert_eq!(parse_path("\"/another/path\""), "/another/path");
+    assert_eq!(word("\"/another/path\""), "/another/path");
 }
 
 #[test]
 fn parse_path_mixed_quotes_removed() {
-    assert!(parse_path("'/mixed\"").is_empty());
-    assert!(parse_path("\"/mixed'").is_empty());
+    assert!(word("'/mixed\"").is_empty());
+    assert!(word("\"/mixed'").is_empty());
 }
 
 #[test]
 fn parse_path_control_chars_filtered() {
-    assert_eq!(parse_path("/path\x00with\x01control"), "/pathwithcontrol");
+    assert_eq!(word("/path\x00with\x01control"), "/pathwithcontrol");
 }
 
 #[test]
 fn parse_bind_valid() {
     assert_eq!(
-        parse_bind("bind /src /dst"),
+        mount("bind /src /dst"),
         Some(MountType::Mount {
             source: "/src".to_string(),
             target: "/dst".to_string(),
+            options: BindOptions::default(),
         })
     );
 }
@@ -45,39 +91,143 @@ fn parse_bind_valid() {
 #[test]
 fn parse_bind_with_quoted_paths() {
     assert_eq!(
-        parse_bind("bind '/source path' \"/target path\""),
+        mount("bind '/source path' \"/target path\""),
         Some(MountType::Mount {
             source: "/source path".to_string(),
             target: "/target path".to_string(),
+            options: BindOptions::default(),
         })
     );
 }
 
 #[test]
 fn parse_bind_missing_target() {
-    assert_eq!(parse_bind("bind /src"), None);
+    assert_eq!(mount("bind /src"), None);
 }
 
 #[test]
 fn parse_bind_missing_source() {
-    assert_eq!(parse_bind("bind"), None);
+    assert_eq!(mount("bind"), None);
 }
 
 #[test]
 fn parse_bind_extra_args_ignored() {
     assert_eq!(
-        parse_bind("bind /src /dst extra"),
+        mount("bind /src /dst extra"),
+        Some(MountType::Mount {
+            source: "/src".to_string(),
+            target: "/dst".to_string(),
+            options: BindOptions::default(),
+        })
+    );
+}
+
+#[test]
+fn parse_bind_options() {
+    assert_eq!(
+        mount(
+            "bind --rw --rbind --nosuid --nodev --noexec --propagation=slave --optional /src /dst"
+        ),
+        Some(MountType::Mount {
+            source: "/src".to_string(),
+            target: "/dst".to_string(),
+            options: BindOptions {
+                rw: true,
+                recursive: true,
+                nosuid: true,
+                nodev: true,
+                noexec: true,
+                propagation: Some(Propagation::Slave),
+                context: None,
+                optional: true,
+            },
+        })
+    );
+    assert_eq!(
+        mount("bind --rw /src /dst").unwrap().to_string(),
+        "/src -> /dst (--rw)"
+    );
+    assert_eq!(
+        statement("bind --ro /src /dst").err(),
+        Some((
+            5,
+            ParseError::InvalidArgument {
+                command: "bind".to_string(),
+                argument: "option",
+                value: "--ro".to_string(),
+            }
+        ))
+    );
+    assert_eq!(
+        statement("bind --propagation=none /src /dst").err(),
+        Some((
+            5,
+            ParseError::InvalidArgument {
+                command: "bind".to_string(),
+                argument: "propagation",
+                value: "none".to_string(),
+            }
+        ))
+    );
+    assert_eq!(
+        mount("bind --context=u:object_r:system_file:s0 /src /dst"),
         Some(MountType::Mount {
             source: "/src".to_string(),
             target: "/dst".to_string(),
+            options: BindOptions {
+                context: Some("u:object_r:system_file:s0".to_string()),
+                ..BindOptions::default()
+            },
         })
     );
+    assert!(matches!(
+        statement("bind --context= /src /dst"),
+        Err((
+            5,
+            ParseError::InvalidArgument {
+                argument: "context",
+                ..
+            }
+        ))
+    ));
+    assert!(mount("bind --rw /src").is_none());
+}
+
+#[test]
+fn parse_bind_end_of_options() {
+    let line = |input: &str| parse_line(input, &Variables::default()).map(|it| it.statement);
+
+    assert!(matches!(
+        line("bind --rw -- --src /dst"),
+        Ok(Statement::Mount(MountType::Mount { source, target, options }))
+            if source == "--src" && target == "/dst" && options.rw
+    ));
+    assert!(matches!(
+        line("bind -- /src /dst"),
+        Ok(Statement::Mount(MountType::Mount { source, options, .. }))
+            if source == "/src" && options.is_default()
+    ));
+    assert!(matches!(
+        line("bind -- --rw /dst"),
+        Ok(Statement::Mount(MountType::Mount { source, options, .. }))
+            if source == "--rw" && options.is_default()
+    ));
+    assert!(matches!(
+        line("bind -- /src"),
+        Err((
+            _,
+            ParseError::MissingArgument {
+                argument: "target",
+                ..
+            }
+        ))
+    ));
 }
 
 #[test]
 fn parse_ignore_valid() {
     assert_eq!(
-        parse_ignore("ignore /path/to/ignore"),
+        mount("ignore /path/to/ignore"),
         Some(MountType::Ignore {
             source: "/path/to/ignore".to_string(),
         })
@@ -86,13 +236,13 @@ fn parse_ignore_valid() {
 
 #[test]
 fn parse_ignore_missing_source() {
-    assert_eq!(parse_ignore("ignore"), None);
+    assert_eq!(mount("ignore"), None);
 }
 
 #[test]
 fn parse_ignore_with_quoted_path() {
     assert_eq!(
-        parse_ignore("ignore '/quoted path'"),
+        mount("ignore '/quoted path'"),
         Some(MountType::Ignore {
             source: "/quoted path".to_string(),
         })
@@ -109,13 +259,14 @@ fn parse_multiple_commands() {
         # another comment
         bind '/app/data' '/mnt/data'
     ";
-    let result = parse(content);
+    let result = commands(content);
     assert_eq!(result.len(), 3);
     assert_eq!(
         result[0],
         MountType::Mount {
             source: "/src".to_string(),
             target: "/dst".to_string(),
+            options: BindOptions::default(),
         }
     );
     assert_eq!(
@@ -129,26 +280,28 @@ fn parse_multiple_commands() {
         MountType::Mount {
             source: "/app/data".to_string(),
             target: "/mnt/data".to_string(),
+            options: BindOptions::default(),
         }
     );
 }
 
 #[test]
 fn parse_empty_content() {
-    assert!(parse("").is_empty());
+    assert!(commands("").is_empty());
 }
 
 #[test]
 fn parser_custom_file_valid() {
     let mut tempfile = tempfile::Builder::new().tempfile().unwrap();
     tempfile.write_all(b"bind /a /b\nignore /c\n").unwrap();
-    let result = parser_custom(tempfile.path());
+    let result = file_commands(tempfile.path());
     assert_eq!(result.len(), 2);
     assert_eq!(
         result[0],
         MountType::Mount {
             source: "/a".to_string(),
             target: "/b".to_string(),
+            options: BindOptions::default(),
         }
     );
     assert_eq!(
@@ -161,14 +314,14 @@ fn parser_custom_file_valid() {
 
 #[test]
 fn parser_custom_file_not_found() {
-    assert!(parser_custom("/nonexistent/path/to/file").is_empty());
+    assert!(file_commands("/nonexistent/path/to/file").is_empty());
 }
 
 #[test]
 fn parse_file_valid() {
     let tempfile = tempfile::Builder::new().tempfile().unwrap();
     assert_eq!(
-        parse_file(&format!("file {}", tempfile.path().to_string_lossy())),
+        include(&format!("file {}", tempfile.path().to_string_lossy())),
         Some(tempfile.path().to_string_lossy().to_string())
     );
 }
@@ -176,29 +329,29 @@ fn parse_file_valid() {
 #[test]
 fn parse_file_with_quoted_path() {
     assert_eq!(
-        parse_file("file '/path with spaces'"),
+        include("file '/path with spaces'"),
         Some("/path with spaces".to_string())
     );
     assert_eq!(
-        parse_file("file \"/another/path\""),
+        include("file \"/another/path\""),
         Some("/another/path".to_string())
     );
 }
 
 #[test]
 fn parse_file_missing_path() {
-    assert_eq!(parse_file("file"), None);
+    assert_eq!(include("file"), None);
 }
 
 #[test]
 fn parse_file_empty_quoted_path() {
-    assert_eq!(parse_file("file ''"), None);
+    assert_eq!(include("file ''"), None);
 }
 
 #[test]
 fn parse_file_add_keyword_supported() {
     assert_eq!(
-        parse_file("add /path/to/config"),
+        include("add /path/to/config"),
         Some("/path/to/config".to_string())
     );
 }
@@ -206,7 +359,7 @@ fn parse_file_add_keyword_supported() {
 #[test]
 fn parse_file_with_quoted_path_for_add() {
     assert_eq!(
-        parse_file("add '/quoted path'"),
+        include("add '/quoted path'"),
         Some("/quoted path".to_string())
     );
 }
@@ -216,14 +369,14 @@ fn parse_file_command_inclusion() {
     let mut temp = tempfile::Builder::new().tempfile().unwrap();
     temp.write_all(b"bind /a /b\nignore /c\n").unwrap();
     let main_content = format!("file {}", temp.path().to_str().unwrap());
-    FILES.lock().clear();
-    let result = parse(&main_content);
+    let result = commands(&main_content);
     assert_eq!(result.len(), 2);
     assert_eq!(
         result[0],
         MountType::Mount {
             source: "/a".to_string(),
             target: "/b".to_string(),
+            options: BindOptions::default(),
         }
     );
     assert_eq!(
@@ -239,6 +392,7 @@ fn mount_type_display() {
     let mount = MountType::Mount {
         source: "s".into(),
         target: "t".into(),
+        options: BindOptions::default(),
     };
     assert_eq!(format!("{}", mount), "s -> t");
     assert_eq!(
@@ -246,3 +400,684 @@ fn mount_type_display() {
         "ignored x"
     );
 }
+
+#[test]
+fn parse_report_records_spans_and_errors() {
+    let mut temp = tempfile::Builder::new().tempfile().unwrap();
+    temp.write_all(b"# comment\n  bind /a /b\nmount /c\nignore \"/mixed'\nbind /a\nbind /a '/b\n")
+        .unwrap();
+    let span = |line, column| Span {
+        file: temp.path().to_path_buf(),
+        line,
+        column,
+    };
+
+    let report = ParseReport::from_file(temp.path());
+    assert_eq!(
+        report.commands,
+        vec![Command {
+            span: span(2, 3),
+            kind: MountType::Mount {
+                source: "/a".to_string(),
+                target: "/b".to_string(),
+                options: BindOptions::default(),
+            },
+            conditional: false,
+            expanded: false,
+            module: None,
+        }]
+    );
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|it| (it.span.clone(), it.error.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                span(3, 1),
+                ParseError::UnknownCommand {
+                    verb: "mount".to_string()
+                }
+            ),
+            (
+                span(4, 8),
+                ParseError::MixedQuotes {
+                    token: "\"/mixed'".to_string()
+                }
+            ),
+            (
+                span(5, 8),
+                ParseError::MissingArgument {
+                    command: "bind".to_string(),
+                    argument: "target"
+                }
+            ),
+            (
+                span(6, 9),
+                ParseError::UnbalancedQuote {
+                    token: "'/b".to_string()
+                }
+            ),
+        ]
+    );
+    assert_eq!(report.errors[0].message, "unknown command \"mount\"");
+}
+
+#[test]
+fn parse_report_spans_point_into_included_files() {
+    let mut included = tempfile::Builder::new().tempfile().unwrap();
+    included.write_all(b"\nignore /c\n").unwrap();
+    let mut main = tempfile::Builder::new().tempfile().unwrap();
+    main.write_all(
+        format!("add {}\nadd /nonexistent/list\n", included.path().display()).as_bytes(),
+    )
+    .unwrap();
+
+    let report = ParseReport::from_file(main.path());
+    assert_eq!(report.commands.len(), 1);
+    assert_eq!(report.commands[0].span.file, included.path());
+    assert_eq!(report.commands[0].span.line, 2);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].span.file, main.path());
+    assert_eq!(report.errors[0].span.line, 2);
+    assert!(matches!(report.errors[0].error, ParseError::Include { .. }));
+}
+
+#[test]
+fn tokenizer_handles_escapes_and_adjacent_quotes() {
+    assert_eq!(
+        mount(r#"bind /a\ b '/c d'"e\"f"\#g"#),
+        Some(MountType::Mount {
+            source: "/a b".to_string(),
+            target: "/c de\"f#g".to_string(),
+            options: BindOptions::default(),
+        })
+    );
+    assert_eq!(
+        mount(r"ignore '/no \escape in single'"),
+        Some(MountType::Ignore {
+            source: r"/no \escape in single".to_string(),
+        })
+    );
+    assert_eq!(
+        mount(r#"ignore "/keep \n other \\ escapes""#),
+        Some(MountType::Ignore {
+            source: r"/keep \n other \ escapes".to_string(),
+        })
+    );
+}
+
+#[test]
+fn quote_path_round_trips() {
+    let paths = [
+        "/system/bin/sh",
+        "/data/local/my module name",
+        "/trailing space ",
+        " /leading space",
+        "/it's",
+        "/say \"hi\"",
+        "/#not-a-comment",
+        r"/back\slash",
+        "/$HOME/`cmd`",
+        "/mixed 'single' and \"double\"",
+        "~/home",
+        "/unicode/文件",
+    ];
+
+    for path in paths {
+        let quoted = quote_path(path);
+        assert_eq!(word(&quoted), path, "{quoted}");
+
+        let line = format!("bind {quoted} {quoted}");
+        assert_eq!(
+            mount(&line),
+            Some(MountType::Mount {
+                source: path.to_string(),
+                target: path.to_string(),
+                options: BindOptions::default(),
+            }),
+            "{line}"
+        );
+    }
+
+    assert_eq!(quote_path("/system/bin/sh"), "/system/bin/sh");
+    assert_eq!(quote_path("/it's"), "\"/it's\"");
+}
+
+#[test]
+fn trailing_comment_may_contain_quotes() {
+    assert_eq!(
+        mount("bind /a /b # it's a comment"),
+        Some(MountType::Mount {
+            source: "/a".to_string(),
+            target: "/b".to_string(),
+            options: BindOptions::default(),
+        })
+    );
+    assert_eq!(
+        mount("ignore /a#b"),
+        Some(MountType::Ignore {
+            source: "/a#b".to_string(),
+        })
+    );
+}
+
+#[test]
+fn parse_ignore_re() {
+    assert_eq!(
+        commands(r"ignore-re '\.odex$'"),
+        vec![MountType::IgnoreRe {
+            pattern: r"\.odex$".to_string(),
+        }]
+    );
+    assert_eq!(
+        commands("ignore-target /system/etc/hosts"),
+        vec![MountType::IgnoreTarget {
+            target: "/system/etc/hosts".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn parse_hide_and_replace() {
+    assert_eq!(
+        commands("hide /system/etc/hosts\nreplace '/system/app/Bloat'"),
+        vec![
+            MountType::Hide {
+                target: "/system/etc/hosts".to_string(),
+            },
+            MountType::Replace {
+                target: "/system/app/Bloat".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        MountType::Hide {
+            target: "/vendor/etc/a".to_string(),
+        }
+        .to_string(),
+        "hidden /vendor/etc/a"
+    );
+}
+
+#[test]
+fn parse_creation_directives() {
+    assert_eq!(
+        commands(
+            "symlink ../lib/libfoo.so /system/lib64/libfoo.so\n\
+             mkdir /system/etc/extra\n\
+             mkdir /system/etc/extra 0750 1000 1000 u:object_r:system_file:s0\n\
+             tmpfs /system/etc/scratch 16M"
+        ),
+        vec![
+            MountType::Symlink {
+                link: "../lib/libfoo.so".to_string(),
+                target: "/system/lib64/libfoo.so".to_string(),
+            },
+            MountType::Mkdir {
+                target: "/system/etc/extra".to_string(),
+                mode: None,
+                uid: None,
+                gid: None,
+                context: None,
+            },
+            MountType::Mkdir {
+                target: "/system/etc/extra".to_string(),
+                mode: Some(0o750),
+                uid: Some(1000),
+                gid: Some(1000),
+                context: Some("u:object_r:system_file:s0".to_string()),
+            },
+            MountType::Tmpfs {
+                target: "/system/etc/scratch".to_string(),
+                size: Some("16M".to_string()),
+            },
+        ]
+    );
+
+    let invalid = |line: &str, argument, value: &str| {
+        assert_eq!(
+            statement(line).err().map(|it| it.1),
+            Some(ParseError::InvalidArgument {
+                command: line.split(' ').next().unwrap().to_string(),
+                argument,
+                value: value.to_string(),
+            }),
+            "{line}"
+        );
+    };
+    invalid("mkdir /system/etc/extra 0789", "mode", "0789");
+    invalid("mkdir /system/etc/extra 17777", "mode", "17777");
+    invalid("mkdir /system/etc/extra 0755 root", "uid", "root");
+    invalid("tmpfs /system/etc/scratch 16MB", "size", "16MB");
+    invalid("tmpfs /system/etc/scratch M", "size", "M");
+    assert!(matches!(
+        statement("symlink /system/lib64/libfoo.so"),
+        Err((
+            _,
+            ParseError::MissingArgument {
+                argument: "target",
+                ..
+            }
+        ))
+    ));
+}
+
+#[test]
+fn if_blocks_select_commands() {
+    let content = r"
+ignore /always
+if root ksu
+    ignore /ksu
+    if not exists /
+        ignore /never
+    else
+        ignore /nested
+    endif
+else
+    ignore /not-ksu
+    file /nonexistent/skipped
+endif
+";
+    let mut report = ParseReport::default();
+    report.parse(
+        Path::new("custom"),
+        content,
+        &Facts::load("/", Some("ksu")),
+        false,
+    );
+
+    assert!(report.errors.is_empty());
+    assert_eq!(
+        report
+            .commands
+            .iter()
+            .map(|it| (it.kind.to_string(), it.conditional))
+            .collect::<Vec<_>>(),
+        vec![
+            ("ignored /always".to_string(), false),
+            ("ignored /ksu".to_string(), true),
+            ("ignored /nested".to_string(), true),
+        ]
+    );
+}
+
+#[test]
+fn unbalanced_blocks_are_errors() {
+    let mut report = ParseReport::default();
+    report.parse(
+        Path::new("custom"),
+        "endif\nif root ksu\nelse\nelse\n",
+        &Facts::default(),
+        false,
+    );
+
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|it| (it.span.line, it.error.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                1,
+                ParseError::UnexpectedBlock {
+                    keyword: "endif".to_string()
+                }
+            ),
+            (
+                4,
+                ParseError::UnexpectedBlock {
+                    keyword: "else".to_string()
+                }
+            ),
+            (2, ParseError::UnclosedIf),
+        ]
+    );
+}
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn expands_variables_outside_single_quotes() {
+    let content = r#"
+set APP ${MODULES}/app
+set NAME "my app"
+ignore ${APP}/"${NAME}"
+ignore '${APP}'
+ignore \${APP}
+ignore "$APP"
+bind ${MODULE:example}/hosts ${CONFIG_DIR}/hosts
+"#;
+    assert_eq!(
+        commands(content),
+        vec![
+            MountType::Ignore {
+                source: format!("{}/app/my app", defs::MODULE_PATH),
+            },
+            MountType::Ignore {
+                source: "${APP}".to_string(),
+            },
+            MountType::Ignore {
+                source: "${APP}".to_string(),
+            },
+            MountType::Ignore {
+                source: "$APP".to_string(),
+            },
+            MountType::Mount {
+                source: format!("{}/example/hosts", defs::MODULE_PATH),
+                target: format!("{}/hosts", defs::CONFIG_DIR),
+                options: BindOptions::default(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn variable_errors() {
+    let mut report = ParseReport::default();
+    report.parse(
+        Path::new("custom"),
+        "ignore /a/${UNSET}\nset MODULES /x\nset 1X /x\nignore ${APP\nignore ${MODULE:../x}\n\
+         if root magisk\nset LATER /x\nignore ${LATER}\nendif\n",
+        &Facts::default(),
+        false,
+    );
+
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|it| (it.span.line, it.span.column, it.error.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                1,
+                11,
+                ParseError::UndefinedVariable {
+                    name: "UNSET".to_string()
+                }
+            ),
+            (
+                2,
+                1,
+                ParseError::InvalidVariable {
+                    name: "MODULES".to_string()
+                }
+            ),
+            (
+                3,
+                1,
+                ParseError::InvalidVariable {
+                    name: "1X".to_string()
+                }
+            ),
+            (
+                4,
+                8,
+                ParseError::UnclosedVariable {
+                    token: "${APP".to_string()
+                }
+            ),
+            (
+                5,
+                8,
+                ParseError::UndefinedVariable {
+                    name: "MODULE:../x".to_string()
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn skipped_if_with_undefined_variable_keeps_nesting() {
+    let mut report = ParseReport::default();
+    report.parse(
+        Path::new("custom"),
+        "if prop x a\nset FOO /a\nif exists ${FOO}\nelse\nbind /a /b\nendif\nendif\nignore /after\n",
+        &Facts::default(),
+        false,
+    );
+
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    assert_eq!(
+        report
+            .commands
+            .iter()
+            .map(|it| it.kind.to_string())
+            .collect::<Vec<_>>(),
+        vec!["ignored /after"]
+    );
+}
+
+#[test]
+fn broken_if_takes_neither_branch() {
+    let mut report = ParseReport::default();
+    report.parse(
+        Path::new("custom"),
+        "if bogus_condition foo\nhide /system/a\nelse\nhide /system/b\nendif\nhide /system/c\n",
+        &Facts::default(),
+        false,
+    );
+
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|it| it.span.line)
+            .collect::<Vec<_>>(),
+        vec![1]
+    );
+    assert_eq!(
+        report
+            .commands
+            .iter()
+            .map(|it| it.kind.to_string())
+            .collect::<Vec<_>>(),
+        vec!["hidden /system/c"]
+    );
+}
+
+#[test]
+fn loads_drop_ins_relative_and_glob_includes() {
+    let temp = tempfile::tempdir().unwrap();
+    let custom = temp.path().join("custom");
+    fs::create_dir_all(temp.path().join("custom.d")).unwrap();
+    fs::create_dir_all(temp.path().join("rules")).unwrap();
+    fs::write(&custom, "ignore /main\nfile rules/*.rules\n").unwrap();
+    fs::write(temp.path().join("rules/b.rules"), "ignore /b\n").unwrap();
+    fs::write(
+        temp.path().join("rules/a.rules"),
+        "ignore /a\nadd ../extra\n",
+    )
+    .unwrap();
+    fs::write(temp.path().join("extra"), "ignore /extra\n").unwrap();
+    fs::write(
+        temp.path().join("custom.d/20-second.list"),
+        "ignore /second\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join("custom.d/10-first.list"),
+        "ignore /first\n",
+    )
+    .unwrap();
+    fs::write(temp.path().join("custom.d/ignored.txt"), "ignore /txt\n").unwrap();
+
+    let report = ParseReport::from_file_with(&custom, &Facts::default(), Variables::default());
+    assert!(report.errors.is_empty());
+    assert_eq!(
+        report
+            .commands
+            .iter()
+            .map(|it| it.kind.to_string())
+            .collect::<Vec<_>>(),
+        ["/main", "/a", "/extra", "/b", "/first", "/second"].map(|it| format!("ignored {it}"))
+    );
+}
+
+#[test]
+fn reports_include_cycles_and_parses_each_file_once() {
+    let temp = tempfile::tempdir().unwrap();
+    let custom = temp.path().join("custom");
+    fs::write(&custom, "file a\nfile b\n").unwrap();
+    fs::write(temp.path().join("a"), "file b\nignore /a\n").unwrap();
+    fs::write(temp.path().join("b"), "ignore /b\nfile a\n").unwrap();
+
+    let report = ParseReport::from_file_with(&custom, &Facts::default(), Variables::default());
+    let root = fs::canonicalize(temp.path()).unwrap();
+    let chain = ["a", "b", "a"]
+        .iter()
+        .map(|it| root.join(it).display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ");
+
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|it| it.error.clone())
+            .collect::<Vec<_>>(),
+        vec![ParseError::IncludeCycle { chain }]
+    );
+    assert_eq!(
+        report
+            .commands
+            .iter()
+            .map(|it| it.kind.to_string())
+            .collect::<Vec<_>>(),
+        ["/b", "/a"].map(|it| format!("ignored {it}"))
+    );
+}
+
+#[test]
+fn diamond_includes_emit_shared_files_once() {
+    let temp = tempfile::tempdir().unwrap();
+    let custom = temp.path().join("custom");
+    fs::write(&custom, "file b\nfile c\n").unwrap();
+    fs::write(temp.path().join("b"), "ignore /b\nfile d\n").unwrap();
+    fs::write(temp.path().join("c"), "ignore /c\nfile ./d\n").unwrap();
+    fs::write(temp.path().join("d"), "bind /src /dst\n").unwrap();
+
+    let report = ParseReport::from_file_with(&custom, &Facts::default(), Variables::default());
+    assert!(report.errors.is_empty());
+    assert_eq!(
+        report
+            .commands
+            .iter()
+            .map(|it| it.kind.to_string())
+            .collect::<Vec<_>>(),
+        ["ignored /b", "/src -> /dst", "ignored /c"]
+    );
+}
+
+#[test]
+fn module_lists_are_owned_and_resolve_relative_sources() {
+    let temp = tempfile::tempdir().unwrap();
+    let custom = temp.path().join("custom");
+    let module = temp.path().join("example");
+    fs::create_dir_all(&module).unwrap();
+    fs::write(&custom, "set DIR /user\nignore ${DIR}/a\n").unwrap();
+    fs::write(
+        module.join(defs::MODULE_LIST_FILE_NAME),
+        "bind files/hosts /system/etc/hosts\nignore /abs\nignore ${DIR}\nset DIR /module\n",
+    )
+    .unwrap();
+
+    let facts = Facts::default();
+    let mut report = ParseReport::from_file_with(&custom, &facts, Variables::default());
+    report.add_module_list("example", &module, &facts);
+    report.add_module_list("missing", &temp.path().join("missing"), &facts);
+
+    assert_eq!(
+        report
+            .commands
+            .iter()
+            .map(|it| (it.module.as_deref(), it.kind.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                None,
+                MountType::Ignore {
+                    source: "/user/a".to_string(),
+                }
+            ),
+            (
+                Some("example"),
+                MountType::Mount {
+                    source: module.join("files/hosts").display().to_string(),
+                    target: "/system/etc/hosts".to_string(),
+                    options: BindOptions::default(),
+                }
+            ),
+            (
+                Some("example"),
+                MountType::Ignore {
+                    source: "/abs".to_string(),
+                }
+            ),
+        ]
+    );
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|it| it.error.clone())
+            .collect::<Vec<_>>(),
+        vec![ParseError::UndefinedVariable {
+            name: "DIR".to_string()
+        }]
+    );
+    assert_eq!(report.variables.get("DIR").as_deref(), Some("/user"));
+}
+
+#[test]
+fn builtin_variables_follow_paths() {
+    let temp = tempfile::tempdir().unwrap();
+    let custom = temp.path().join("custom");
+    let dir = temp.path().join("example");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        &custom,
+        "bind ${MODULES}/example/hosts ${CONFIG_DIR}/hosts\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join(defs::MODULE_LIST_FILE_NAME),
+        "ignore ${MODULES}/a\n",
+    )
+    .unwrap();
+
+    let paths = Paths {
+        module_dir: PathBuf::from("/staging/modules"),
+        state_dir: PathBuf::from("/staging/state"),
+        ..Paths::default()
+    };
+    assert_eq!(
+        Variables::new(&paths).get("MODULE:other").as_deref(),
+        Some("/staging/modules/other")
+    );
+
+    let facts = Facts::default();
+    let mut report = ParseReport::from_file_with(&custom, &facts, Variables::new(&paths));
+    report.add_module_list("example", &dir, &facts);
+
+    assert_eq!(
+        report
+            .into_commands()
+            .into_iter()
+            .map(|it| it.kind)
+            .collect::<Vec<_>>(),
+        vec![
+            MountType::Mount {
+                source: "/staging/modules/example/hosts".to_string(),
+                target: "/staging/state/hosts".to_string(),
+                options: BindOptions::default(),
+            },
+            MountType::Ignore {
+                source: "/staging/modules/a".to_string(),
+            },
+        ]
+    );
+}
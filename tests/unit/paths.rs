@@ -0,0 +1,66 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use super::*;
+
+#[test]
+fn defaults_without_flags_or_environment() {
+    let paths = Paths::resolve(PathArgs::default(), |_| None);
+
+    assert_eq!(paths, Paths::default());
+    assert_eq!(
+        paths.custom_list(),
+        Path::new("/data/adb/magic_mount/custom")
+    );
+    assert_eq!(
+        paths.config_file,
+        Path::new("/data/adb/magic_mount/config.toml")
+    );
+    assert_eq!(paths.work_dir(), Path::new("/debug_ramdisk/workdir"));
+}
+
+#[test]
+fn flags_win_over_environment() {
+    let args = PathArgs {
+        module_dir: Some(PathBuf::from("/staging/modules")),
+        work_root: Some(PathBuf::from("/tmp/work")),
+        ..PathArgs::default()
+    };
+    let env = |name: &str| match name {
+        "MAGIC_MOUNT_MODULE_DIR" => Some(OsString::from("/env/modules")),
+        "MAGIC_MOUNT_STATE_DIR" => Some(OsString::from("/env/state")),
+        _ => None,
+    };
+    let paths = Paths::resolve(args, env);
+
+    assert_eq!(paths.module_dir, Path::new("/staging/modules"));
+    assert_eq!(paths.state_dir, Path::new("/env/state"));
+    assert_eq!(paths.work_root, Path::new("/tmp/work"));
+    assert_eq!(paths.config_file, Path::new("/env/state/config.toml"));
+    assert_eq!(paths.rules_dir(), Path::new("/env/state/rules"));
+    assert_eq!(paths.umount_list(), Path::new("/env/state/umount.list"));
+}
+
+#[test]
+fn config_flag_is_independent_of_state_dir() {
+    let args = PathArgs {
+        config: Some(PathBuf::from("/etc/magic.toml")),
+        state_dir: Some(PathBuf::from("/state")),
+        ..PathArgs::default()
+    };
+    let paths = Paths::resolve(args, |_| None);
+
+    assert_eq!(paths.config_file, Path::new("/etc/magic.toml"));
+    assert_eq!(paths.custom_list(), Path::new("/state/custom"));
+}
+
+#[test]
+fn empty_values_fall_back() {
+    let args = PathArgs {
+        state_dir: Some(PathBuf::from("")),
+        ..PathArgs::default()
+    };
+    let paths = Paths::resolve(args, |_| Some(OsString::new()));
+
+    assert_eq!(paths, Paths::default());
+}
@@ -0,0 +1,182 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use std::fs;
+
+use super::*;
+
+fn system_with_etc(etc: Node) -> Node {
+    let mut system = Node::new_for_test("system", NodeFileType::Directory, None);
+    system.children.insert(etc.name.clone(), etc);
+    system
+}
+
+#[test]
+fn binds_existing_file_in_place() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("root");
+    let module = temp.path().join("module/system/etc");
+    fs::create_dir_all(root.join("system/etc")).unwrap();
+    fs::create_dir_all(&module).unwrap();
+    fs::write(root.join("system/etc/hosts"), "").unwrap();
+    fs::write(module.join("hosts"), "").unwrap();
+
+    let mut etc = Node::new_for_test("etc", NodeFileType::Directory, Some(module.clone()));
+    etc.children.insert(
+        "hosts".to_string(),
+        Node::new_for_test(
+            "hosts",
+            NodeFileType::RegularFile,
+            Some(module.join("hosts")),
+        ),
+    );
+
+    let mut planner = Planner::default();
+    planner
+        .node(
+            &system_with_etc(etc),
+            &root,
+            &temp.path().join("work"),
+            false,
+        )
+        .unwrap();
+
+    assert_eq!(
+        planner.ops,
+        vec![Operation::Bind {
+            source: module.join("hosts"),
+            target: root.join("system/etc/hosts"),
+        }]
+    );
+}
+
+#[test]
+fn builds_tmpfs_for_new_file() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("root");
+    let work = temp.path().join("work");
+    let module = temp.path().join("module/system/etc");
+    fs::create_dir_all(root.join("system/etc")).unwrap();
+    fs::create_dir_all(&module).unwrap();
+    fs::write(root.join("system/etc/hosts"), "").unwrap();
+    fs::write(module.join("new"), "").unwrap();
+
+    let mut etc = Node::new_for_test("etc", NodeFileType::Directory, Some(module.clone()));
+    etc.children.insert(
+        "new".to_string(),
+        Node::new_for_test("new", NodeFileType::RegularFile, Some(module.join("new"))),
+    );
+
+    let mut planner = Planner::default();
+    planner
+        .node(&system_with_etc(etc), &root, &work, false)
+        .unwrap();
+
+    assert_eq!(
+        planner.ops,
+        vec![
+            Operation::Tmpfs {
+                path: root.join("system/etc"),
+                work_dir: work.join("system/etc"),
+            },
+            Operation::Mirror {
+                source: root.join("system/etc/hosts"),
+                target: work.join("system/etc/hosts"),
+            },
+            Operation::Bind {
+                source: module.join("new"),
+                target: work.join("system/etc/new"),
+            },
+            Operation::Move {
+                source: work.join("system/etc"),
+                target: root.join("system/etc"),
+            },
+        ]
+    );
+}
+
+#[test]
+fn skips_children_that_need_tmpfs_on_unowned_dir() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("root");
+    fs::create_dir_all(root.join("system")).unwrap();
+
+    let system = system_with_etc(Node::new_for_test(
+        "missing",
+        NodeFileType::RegularFile,
+        Some(temp.path().join("module/system/missing")),
+    ));
+
+    let mut planner = Planner::default();
+    planner
+        .node(&system, &root, &temp.path().join("work"), false)
+        .unwrap();
+
+    assert_eq!(
+        planner.ops,
+        vec![Operation::Skip {
+            path: root.join("system/missing"),
+        }]
+    );
+}
+
+#[test]
+fn operation_display() {
+    let op = Operation::Whiteout {
+        path: PathBuf::from("/system/app/Foo"),
+    };
+    assert_eq!(op.to_string(), "whiteout /system/app/Foo");
+}
+
+#[test]
+fn records_failed_children_in_the_plan() {
+    let temp = tempfile::tempdir().unwrap();
+    let root = temp.path().join("root");
+    let module = temp.path().join("module/system/etc");
+    fs::create_dir_all(root.join("system/etc")).unwrap();
+    fs::write(root.join("system/etc/hosts"), "").unwrap();
+
+    let mut etc = Node::new_for_test("etc", NodeFileType::Directory, Some(module));
+    etc.children.insert(
+        "hosts".to_string(),
+        Node::new_for_test("hosts", NodeFileType::RegularFile, None),
+    );
+
+    let mut planner = Planner::default();
+    planner
+        .node(
+            &system_with_etc(etc),
+            &root,
+            &temp.path().join("work"),
+            false,
+        )
+        .unwrap();
+
+    let path = root.join("system/etc/hosts");
+    assert_eq!(
+        planner.ops,
+        vec![Operation::Failed {
+            error: format!(
+                "magic mount {}/hosts: cannot mount root file {:?}!",
+                root.join("system/etc").display(),
+                path.display().to_string()
+            ),
+            path,
+        }]
+    );
+}
+
+#[test]
+fn tmpfs_decision_leaves_the_node_untouched() {
+    let temp = tempfile::tempdir().unwrap();
+    let system = system_with_etc(Node::new_for_test(
+        "missing",
+        NodeFileType::RegularFile,
+        Some(temp.path().join("module/system/missing")),
+    ));
+
+    let (tmpfs, skipped) = tmpfs_decision(&system, temp.path(), false);
+    assert!(!tmpfs);
+    assert_eq!(skipped, vec!["missing".to_string()]);
+    assert!(!system.children["missing"].skip);
+}
@@ -0,0 +1,37 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use super::*;
+
+#[test]
+fn records_phases_errors_and_round_trips() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("last_run.json");
+    let mut report = Report::new(&Config::default());
+
+    assert_eq!(report.time("scan", || 42), 42);
+    let e = anyhow::anyhow!("bind failed").context("magic mount /system/etc");
+    report.error("magic_mount", &e);
+    report.finish(MountStats {
+        mounted_files: 3,
+        mounted_symlinks: 1,
+        ignored_files: 2,
+    });
+    report.save(&path).unwrap();
+
+    let loaded: Report = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(loaded.phases.len(), 1);
+    assert_eq!(loaded.phases[0].name, "scan");
+    assert_eq!(loaded.errors[0].phase, "magic_mount");
+    assert_eq!(
+        loaded.errors[0].chain,
+        vec![
+            "magic mount /system/etc".to_string(),
+            "bind failed".to_string()
+        ]
+    );
+    assert_eq!(loaded.counts.mounted_files, 3);
+    assert_eq!(loaded.counts.ignored_files, 2);
+    assert!(loaded.finished_at >= loaded.started_at);
+    assert_eq!(loaded.config.mountsource, "KSU");
+}
@@ -80,7 +80,8 @@ fn test_list_modules_integration() {
     )
     .unwrap();
 
-    let result = list_modules(module_dir, &["vendor".to_string()]);
+    let rules_dir = tmp_dir.path().join("rules");
+    let result = list_modules(module_dir, &rules_dir, &["vendor".to_string()]);
     assert_eq!(result.len(), 5);
     assert_eq!(result[0].id, "test1");
     assert_eq!(result[1].id, "test2");
@@ -89,22 +90,167 @@ fn test_list_modules_integration() {
     assert_eq!(result[4].id, "test6");
     assert!(result[0].is_mounted);
     assert!(result[0].enabled);
-    assert_eq!(result[0].mode, "magic");
+    assert_eq!(result[0].mode, MountMode::Magic);
     assert!(!result[1].is_mounted);
     assert!(!result[1].enabled);
-    assert_eq!(result[1].mode, "ignore");
+    assert_eq!(result[1].mode, MountMode::Ignore);
     assert!(!result[2].is_mounted);
     assert!(result[2].enabled);
-    assert_eq!(result[2].mode, "ignore");
+    assert_eq!(result[2].mode, MountMode::Ignore);
     assert!(!result[3].is_mounted);
     assert!(result[3].enabled);
-    assert_eq!(result[3].mode, "ignore");
+    assert_eq!(result[3].mode, MountMode::Ignore);
     assert!(result[4].is_mounted);
-    assert_eq!(result[4].mode, "magic");
+    assert_eq!(result[4].mode, MountMode::Magic);
 }
 
 #[test]
 fn test_list_modules_empty_dir() {
     let tmp_dir = tempfile::tempdir().unwrap();
-    assert!(list_modules(tmp_dir.path(), &[]).is_empty());
+    assert!(list_modules(tmp_dir.path(), tmp_dir.path(), &[]).is_empty());
+}
+
+#[test]
+fn test_module_rules_round_trip_and_ignore_mode() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let module_dir = tmp_dir.path().join("modules");
+    let rules_dir = tmp_dir.path().join("rules");
+    let test1 = module_dir.join("test1");
+    fs::create_dir_all(test1.join("system")).unwrap();
+    fs::write(
+        test1.join("module.prop"),
+        create_valid_prop_content("test1"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        ModuleRules::load(&rules_dir, "test1"),
+        ModuleRules::default()
+    );
+
+    let rules: ModuleRules =
+        serde_json::from_str(r#"{"default_mode":"ignore","paths":{"system/bin":"magic"}}"#)
+            .unwrap();
+    rules.save(&rules_dir, "test1").unwrap();
+    assert_eq!(ModuleRules::load(&rules_dir, "test1"), rules);
+
+    let result = list_modules(&module_dir, &rules_dir, &[]);
+    assert_eq!(result[0].mode, MountMode::Magic);
+
+    ModuleRules {
+        default_mode: MountMode::Ignore,
+        paths: BTreeMap::new(),
+        ..ModuleRules::default()
+    }
+    .save(&rules_dir, "test1")
+    .unwrap();
+    let result = list_modules(&module_dir, &rules_dir, &[]);
+    assert_eq!(result[0].mode, MountMode::Ignore);
+    assert!(!result[0].is_mounted);
+}
+
+#[test]
+fn test_module_rules_longest_prefix_wins() {
+    let rules = ModuleRules {
+        default_mode: MountMode::Magic,
+        paths: [
+            ("system/etc".to_string(), MountMode::Ignore),
+            ("/system/etc/hosts/".to_string(), MountMode::Magic),
+        ]
+        .into(),
+        ..ModuleRules::default()
+    };
+
+    assert_eq!(rules.mode_for("system/bin/sh"), MountMode::Magic);
+    assert_eq!(rules.mode_for("system/etc/fonts.xml"), MountMode::Ignore);
+    assert_eq!(rules.mode_for("system/etc/hosts"), MountMode::Magic);
+    assert_eq!(rules.mode_for("system/etcetera"), MountMode::Magic);
+    assert!(rules.ignores("system/etc/fonts.xml"));
+    assert!(!rules.ignores("system/etc"));
+}
+
+#[test]
+fn test_module_rules_contexts() {
+    let rules = ModuleRules {
+        contexts: [
+            (
+                "system/vendor/etc".to_string(),
+                "u:object_r:vendor_configs_file:s0".to_string(),
+            ),
+            (
+                "system/vendor/etc/init".to_string(),
+                "u:object_r:system_file:s0".to_string(),
+            ),
+        ]
+        .into(),
+        ..ModuleRules::default()
+    };
+
+    assert_eq!(
+        rules.context_for("system/vendor/etc/thermal.conf"),
+        Some("u:object_r:vendor_configs_file:s0")
+    );
+    assert_eq!(
+        rules.context_for("system/vendor/etc/init/hw.rc"),
+        Some("u:object_r:system_file:s0")
+    );
+    assert_eq!(rules.context_for("system/etc/hosts"), None);
+}
+
+#[test]
+fn test_show_modules_uses_mount_status() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let module_dir = tmp_dir.path().join("modules");
+    for id in ["test1", "test2"] {
+        let module = module_dir.join(id);
+        fs::create_dir_all(module.join("system")).unwrap();
+        fs::write(module.join("module.prop"), create_valid_prop_content(id)).unwrap();
+    }
+    let status_path = tmp_dir.path().join("status.json");
+    fs::write(
+        &status_path,
+        r#"{"test2":{"mounted_files":0,"symlinks":0,"whiteouts":0,"skipped":[],"errors":["bind failed"]}}"#,
+    )
+    .unwrap();
+
+    let modules = list_modules(&module_dir, &tmp_dir.path().join("rules"), &[]);
+    let result = show_modules(modules, &status_path).unwrap();
+    assert!(!result[0].is_mounted);
+    assert!(result[0].status.is_none());
+    assert!(!result[1].is_mounted);
+    assert_eq!(
+        result[1].status.as_ref().unwrap().errors,
+        vec!["bind failed".to_string()]
+    );
+
+    let modules = list_modules(&module_dir, &tmp_dir.path().join("rules"), &[]);
+    let result = show_modules(modules, tmp_dir.path().join("missing.json")).unwrap();
+    assert!(result.iter().all(|module| module.status.is_none()));
+}
+
+#[test]
+fn test_module_lists_skip_disabled_and_skip_mount() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    for (id, flag) in [
+        ("alpha", None),
+        ("beta", Some(defs::DISABLE_FILE_NAME)),
+        ("gamma", Some(defs::REMOVE_FILE_NAME)),
+        ("delta", Some(defs::SKIP_MOUNT_FILE_NAME)),
+        ("epsilon", None),
+    ] {
+        let module = tmp_dir.path().join(id);
+        fs::create_dir_all(&module).unwrap();
+        fs::write(module.join("module.prop"), create_valid_prop_content(id)).unwrap();
+        if id != "epsilon" {
+            fs::write(module.join(defs::MODULE_LIST_FILE_NAME), "ignore a\n").unwrap();
+        }
+        if let Some(flag) = flag {
+            fs::File::create(module.join(flag)).unwrap();
+        }
+    }
+
+    assert_eq!(
+        module_lists(tmp_dir.path()),
+        vec![("alpha".to_string(), tmp_dir.path().join("alpha"))]
+    );
 }
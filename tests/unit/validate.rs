@@ -0,0 +1,290 @@
+// Copyright (C) 2026 meta-magic_mount-rs developers
+// SPDX-License-Identifier: GPL-v3
+
+use super::*;
+use crate::{defs, parser::Variables};
+
+fn messages(diagnostics: &[Diagnostic]) -> Vec<(Option<usize>, Severity, &str)> {
+    diagnostics
+        .iter()
+        .map(|it| (it.line, it.severity, it.message.as_str()))
+        .collect()
+}
+
+fn validate_file<P>(path: P, partitions: &[String]) -> Vec<Diagnostic>
+where
+    P: AsRef<Path>,
+{
+    let report = ParseReport::from_file_with(path, &Facts::default(), Variables::default());
+    validate_list(&report, partitions)
+}
+
+#[test]
+fn config_reports_unknown_keys_and_bad_values() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("config.toml");
+    fs::write(
+        &path,
+        "mountsource = \"my source\"\numount = \"no\"\npartitions = [\"mi_ext\", \"../odm\"]\nunknown = 1\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        messages(&validate_config(&path)),
+        vec![
+            (
+                Some(4),
+                Severity::Warning,
+                "unknown key \"unknown\" is kept but ignored"
+            ),
+            (
+                Some(2),
+                Severity::Error,
+                "invalid umount: invalid type: string \"no\", expected a boolean, the default is used"
+            ),
+            (
+                Some(1),
+                Severity::Error,
+                "invalid mountsource \"my source\""
+            ),
+            (
+                Some(3),
+                Severity::Error,
+                "invalid partition name \"../odm\""
+            ),
+        ]
+    );
+}
+
+#[test]
+fn config_reports_toml_syntax_error_line() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("config.toml");
+    fs::write(&path, "umount = false\npartitions = [\n").unwrap();
+
+    let diagnostics = validate_config(&path);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert!(diagnostics[0].line.is_some());
+}
+
+#[test]
+fn custom_list_rejects_new_entries_in_partition_roots() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("custom");
+    fs::write(
+        &path,
+        "hide /system/build.prop\nmkdir /system/vendor/extra\nsymlink /a /mi_ext/link\n\
+         replace /system/app\nhide /system/etc/hosts\ntmpfs /vendor/etc/scratch\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        messages(&validate_file(&path, &["mi_ext".to_string()])),
+        vec![
+            (
+                Some(1),
+                Severity::Error,
+                "target /system/build.prop is directly in a partition root"
+            ),
+            (
+                Some(2),
+                Severity::Error,
+                "target /system/vendor/extra is directly in a partition root"
+            ),
+            (
+                Some(3),
+                Severity::Error,
+                "target /mi_ext/link is directly in a partition root"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn custom_list_reports_line_diagnostics() {
+    let temp = tempfile::tempdir().unwrap();
+    let source = temp.path().join("source");
+    fs::write(&source, "").unwrap();
+    let path = temp.path().join("custom");
+    fs::write(
+        &path,
+        format!(
+            "# comment\nbind {} /system/etc/hosts\nmount /a /b\nbind 'unbalanced /system/a\nignore \"/mixed'\nbind relative /system/b\nbind {} /data/local\nignore /does/not/exist\nbind {} /mi_ext/a\n",
+            source.display(),
+            source.display(),
+            source.display()
+        ),
+    )
+    .unwrap();
+
+    assert_eq!(
+        messages(&validate_file(&path, &["mi_ext".to_string()])),
+        vec![
+            (Some(3), Severity::Error, "unknown command \"mount\""),
+            (
+                Some(4),
+                Severity::Error,
+                "unbalanced quote in 'unbalanced /system/a"
+            ),
+            (Some(5), Severity::Error, "mixed quotes in \"/mixed'"),
+            (
+                Some(6),
+                Severity::Error,
+                "\"relative\" is not an absolute path"
+            ),
+            (
+                Some(7),
+                Severity::Error,
+                "target /data/local is outside the allowed partitions"
+            ),
+            (
+                Some(8),
+                Severity::Warning,
+                "source /does/not/exist does not exist"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn custom_list_reports_include_cycles() {
+    let temp = tempfile::tempdir().unwrap();
+    let a = temp.path().join("a");
+    let b = temp.path().join("b");
+    fs::write(&a, format!("file {}\n", b.display())).unwrap();
+    fs::write(&b, format!("\nadd {}\n", a.display())).unwrap();
+
+    let diagnostics = validate_file(&a, &[]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].file, b);
+    assert_eq!(diagnostics[0].line, Some(2));
+    assert_eq!(
+        diagnostics[0].message,
+        format!(
+            "include cycle: {} -> {} -> {}",
+            a.display(),
+            b.display(),
+            a.display()
+        )
+    );
+}
+
+#[test]
+fn custom_list_checks_the_branches_in_effect() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("custom");
+    fs::write(
+        &path,
+        "if root ksu\nignore relative\nset DIR /ksu\nelse\nignore-re '('\nignore ${DIR}/a\n\
+         else\nendif\nendif\nif kernel 5.*\n",
+    )
+    .unwrap();
+
+    let diagnostics = validate_file(&path, &[]);
+    assert_eq!(
+        messages(&diagnostics)
+            .into_iter()
+            .map(|(line, _, message)| (line, message.split(':').next().unwrap()))
+            .collect::<Vec<_>>(),
+        vec![
+            (Some(5), "invalid regex ("),
+            (Some(6), "undefined variable DIR"),
+            (Some(7), "else without a matching if"),
+            (Some(9), "endif without a matching if"),
+            (Some(10), "if without a matching endif"),
+        ]
+    );
+}
+
+#[test]
+fn custom_list_follows_relative_includes_and_drop_ins() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("custom");
+    fs::create_dir_all(temp.path().join("custom.d")).unwrap();
+    fs::write(&path, "file extra\nfile none/*.list\n").unwrap();
+    fs::write(temp.path().join("extra"), "ignore relative\n").unwrap();
+    fs::write(temp.path().join("custom.d/a.list"), "mount /a /b\n").unwrap();
+
+    let diagnostics = validate_file(&path, &[]);
+    assert_eq!(
+        diagnostics
+            .iter()
+            .map(|it| (it.file.clone(), it.line, it.severity, it.message.as_str()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                path,
+                Some(2),
+                Severity::Warning,
+                "none/*.list matches no files"
+            ),
+            (
+                temp.path().join("custom.d/a.list"),
+                Some(1),
+                Severity::Error,
+                "unknown command \"mount\""
+            ),
+            (
+                temp.path().join("extra"),
+                Some(1),
+                Severity::Error,
+                "\"relative\" is not an absolute path"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn missing_config_is_not_an_error() {
+    let temp = tempfile::tempdir().unwrap();
+    assert!(validate_config(temp.path().join("config.toml")).is_empty());
+}
+
+#[test]
+fn missing_custom_list_is_not_an_error() {
+    let temp = tempfile::tempdir().unwrap();
+    assert!(validate_file(temp.path().join("custom"), &[]).is_empty());
+}
+
+#[test]
+fn module_lists_are_validated() {
+    let temp = tempfile::tempdir().unwrap();
+    let module = temp.path().join("example");
+    fs::create_dir_all(&module).unwrap();
+    let list = module.join(defs::MODULE_LIST_FILE_NAME);
+    fs::write(
+        &list,
+        "bind files/missing /system/etc/hosts
+hide /data/x
+",
+    )
+    .unwrap();
+
+    let facts = Facts::default();
+    let mut report =
+        ParseReport::from_file_with(temp.path().join("custom"), &facts, Variables::default());
+    report.add_module_list("example", &module, &facts);
+
+    assert_eq!(
+        validate_list(&report, &[])
+            .iter()
+            .map(|it| (it.file.clone(), it.line, it.message.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                list.clone(),
+                Some(1),
+                format!(
+                    "source {} does not exist",
+                    module.join("files/missing").display()
+                )
+            ),
+            (
+                list,
+                Some(2),
+                "target /data/x is outside the allowed partitions".to_string()
+            ),
+        ]
+    );
+}